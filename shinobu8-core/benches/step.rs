@@ -0,0 +1,56 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use shinobu8_core::Emu;
+
+const STEPS: u64 = 100_000;
+
+// A loop over a representative mix of arithmetic, memory, flow and drawing opcodes.
+#[rustfmt::skip]
+const OPCODE_MIX: [u8; 32] = [
+    0x60, 0x05, // V0 = 5
+    0x61, 0x03, // V1 = 3
+    0x80, 0x14, // V0 += V1
+    0x70, 0x01, // V0 += 1
+    0x81, 0x06, // V1 >>= 1
+    0x30, 0x00, // skip if V0 == 0
+    0xA2, 0x00, // I = 0x200
+    0xF0, 0x1E, // I += V0
+    0xC2, 0xFF, // V2 = rand
+    0xD0, 0x15, // draw 5 rows at (V0, V1)
+    0x22, 0x18, // call 0x218
+    0x12, 0x00, // jump 0x200
+    0xA3, 0x00, // I = 0x300
+    0xF2, 0x33, // BCD of V2
+    0xF2, 0x65, // load V0..V2
+    0x00, 0xEE, // return
+];
+
+fn loaded(rom: &[u8]) -> Emu {
+    let mut emu = Emu::new();
+    emu.seed_rng(0);
+    emu.load(rom).unwrap();
+    emu
+}
+
+fn bench_roms(c: &mut Criterion) {
+    let roms: [(&str, &[u8]); 2] = [
+        ("pong2", include_bytes!("../../roms/PONG2")),
+        ("15puzzle", include_bytes!("../../roms/15PUZZLE")),
+    ];
+    for (name, rom) in roms {
+        c.bench_function(&format!("{} {} steps", name, STEPS), |b| {
+            b.iter_batched(
+                || loaded(rom),
+                |mut emu| emu.step_n(black_box(STEPS)).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+}
+
+fn bench_opcode_mix(c: &mut Criterion) {
+    let mut emu = loaded(&OPCODE_MIX);
+    c.bench_function("opcode mix step", |b| b.iter(|| emu.step().unwrap()));
+}
+
+criterion_group!(benches, bench_roms, bench_opcode_mix);
+criterion_main!(benches);
@@ -0,0 +1,252 @@
+use crate::START_ADDR;
+use anyhow::{anyhow, bail, Context};
+use std::collections::HashMap;
+
+/// Assembles a program for the program start address, written with the mnemonics
+/// that [`crate::disasm`] produces. Each line holds at most one instruction, `;`
+/// starts a comment and `name:` defines a label that can be used wherever an
+/// address is expected. `DW` and `DB` emit raw words and bytes.
+pub fn assemble(src: &str) -> anyhow::Result<Vec<u8>> {
+    // Find the address of every label first, so code can jump forward.
+    let mut labels = HashMap::new();
+    let mut addr = START_ADDR as u32;
+    for (i, line) in src.lines().enumerate() {
+        let (label, statement) = split_line(line).with_context(|| context(i, line))?;
+        if let Some(label) = label {
+            if labels.insert(label, addr).is_some() {
+                return Err(anyhow!("duplicate label {}", label)).with_context(|| context(i, line));
+            }
+        }
+        if let Some((mnemonic, _)) = statement {
+            addr += if mnemonic == "DB" { 1 } else { 2 };
+        }
+    }
+
+    let mut rom = Vec::new();
+    for (i, line) in src.lines().enumerate() {
+        if let (_, Some((mnemonic, args))) = split_line(line)? {
+            encode(&mnemonic, &args, &labels, &mut rom).with_context(|| context(i, line))?;
+        }
+    }
+    Ok(rom)
+}
+
+fn context(i: usize, line: &str) -> String {
+    format!("line {}: {}", i + 1, line.trim())
+}
+
+// An upper-cased mnemonic and its arguments.
+type Statement<'a> = (String, Vec<&'a str>);
+
+// Splits a line into its label and statement, either of which may be missing.
+fn split_line(line: &str) -> anyhow::Result<(Option<&str>, Option<Statement<'_>>)> {
+    let code = line.split(';').next().unwrap_or_default().trim();
+    let (label, rest) = match code.split_once(':') {
+        Some((label, rest)) => (Some(label.trim()), rest.trim()),
+        None => (None, code),
+    };
+    if let Some(label) = label {
+        let mut chars = label.chars();
+        let valid = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            bail!("invalid label {:?}", label);
+        }
+    }
+    if rest.is_empty() {
+        return Ok((label, None));
+    }
+    let (mnemonic, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let args = args
+        .split(',')
+        .map(str::trim)
+        .filter(|arg| !arg.is_empty())
+        .collect();
+    Ok((label, Some((mnemonic.to_ascii_uppercase(), args))))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operand {
+    V(u16),
+    Num(u32),
+    Key(&'static str),
+}
+
+const KEYWORDS: [&str; 12] = [
+    "I", "[I]", "DT", "ST", "K", "F", "HF", "B", "R", "PITCH", "AUDIO", "LONG",
+];
+
+impl Operand {
+    fn parse(arg: &str, labels: &HashMap<&str, u32>) -> anyhow::Result<Self> {
+        let upper = arg.to_ascii_uppercase();
+        if let Some(key) = KEYWORDS.iter().find(|&&key| key == upper) {
+            return Ok(Operand::Key(key));
+        }
+        if let [b'V', reg] = upper.as_bytes() {
+            if let Some(x) = (*reg as char).to_digit(16) {
+                return Ok(Operand::V(x as u16));
+            }
+        }
+        if arg.starts_with(|c: char| c.is_ascii_digit()) {
+            let num = match upper.strip_prefix("0X") {
+                Some(hex) => u32::from_str_radix(hex, 16),
+                None => upper.parse(),
+            };
+            return num
+                .map(Operand::Num)
+                .map_err(|_| anyhow!("invalid number {}", arg));
+        }
+        labels
+            .get(arg)
+            .map(|&addr| Operand::Num(addr))
+            .ok_or_else(|| anyhow!("unknown label {}", arg))
+    }
+}
+
+fn limit(value: u32, max: u32) -> anyhow::Result<u16> {
+    if value > max {
+        bail!("{:#X} is larger than {:#X}", value, max);
+    }
+    Ok(value as u16)
+}
+
+fn encode(
+    mnemonic: &str,
+    args: &[&str],
+    labels: &HashMap<&str, u32>,
+    rom: &mut Vec<u8>,
+) -> anyhow::Result<()> {
+    use Operand::*;
+
+    let ops = args
+        .iter()
+        .map(|arg| Operand::parse(arg, labels))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let addr = |a: u32| limit(a, 0xFFF);
+    let byte = |b: u32| limit(b, 0xFF);
+    let nibble = |n: u32| limit(n, 0xF);
+    let xy = |x: u16, y: u16| x << 8 | y << 4;
+
+    let word = match (mnemonic, ops.as_slice()) {
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("SCD", [Num(n)]) => 0x00C0 | nibble(*n)?,
+        ("SCR", []) => 0x00FB,
+        ("SCL", []) => 0x00FC,
+        ("EXIT", []) => 0x00FD,
+        ("LOW", []) => 0x00FE,
+        ("HIGH", []) => 0x00FF,
+        ("SYS", [Num(a)]) => addr(*a)?,
+        ("JP", [Num(a)]) => 0x1000 | addr(*a)?,
+        ("CALL", [Num(a)]) => 0x2000 | addr(*a)?,
+        ("SE", [V(x), Num(k)]) => 0x3000 | x << 8 | byte(*k)?,
+        ("SNE", [V(x), Num(k)]) => 0x4000 | x << 8 | byte(*k)?,
+        ("SE", [V(x), V(y)]) => 0x5000 | xy(*x, *y),
+        ("LD", [V(x), Num(k)]) => 0x6000 | x << 8 | byte(*k)?,
+        ("ADD", [V(x), Num(k)]) => 0x7000 | x << 8 | byte(*k)?,
+        ("LD", [V(x), V(y)]) => 0x8000 | xy(*x, *y),
+        ("OR", [V(x), V(y)]) => 0x8001 | xy(*x, *y),
+        ("AND", [V(x), V(y)]) => 0x8002 | xy(*x, *y),
+        ("XOR", [V(x), V(y)]) => 0x8003 | xy(*x, *y),
+        ("ADD", [V(x), V(y)]) => 0x8004 | xy(*x, *y),
+        ("SUB", [V(x), V(y)]) => 0x8005 | xy(*x, *y),
+        ("SHR", [V(x), V(y)]) => 0x8006 | xy(*x, *y),
+        ("SUBN", [V(x), V(y)]) => 0x8007 | xy(*x, *y),
+        ("SHL", [V(x), V(y)]) => 0x800E | xy(*x, *y),
+        ("SNE", [V(x), V(y)]) => 0x9000 | xy(*x, *y),
+        ("LD", [Key("I"), Num(a)]) => 0xA000 | addr(*a)?,
+        ("JP", [V(0), Num(a)]) => 0xB000 | addr(*a)?,
+        ("RND", [V(x), Num(k)]) => 0xC000 | x << 8 | byte(*k)?,
+        ("DRW", [V(x), V(y), Num(n)]) => 0xD000 | xy(*x, *y) | nibble(*n)?,
+        ("SKP", [V(x)]) => 0xE09E | x << 8,
+        ("SKNP", [V(x)]) => 0xE0A1 | x << 8,
+        ("LD", [Key("I"), Key("LONG")]) => 0xF000,
+        ("PLANE", [Num(n)]) => 0xF001 | nibble(*n)? << 8,
+        ("LD", [Key("AUDIO"), Key("[I]")]) => 0xF002,
+        ("LD", [V(x), Key("DT")]) => 0xF007 | x << 8,
+        ("LD", [V(x), Key("K")]) => 0xF00A | x << 8,
+        ("LD", [Key("DT"), V(x)]) => 0xF015 | x << 8,
+        ("LD", [Key("ST"), V(x)]) => 0xF018 | x << 8,
+        ("ADD", [Key("I"), V(x)]) => 0xF01E | x << 8,
+        ("LD", [Key("F"), V(x)]) => 0xF029 | x << 8,
+        ("LD", [Key("HF"), V(x)]) => 0xF030 | x << 8,
+        ("LD", [Key("B"), V(x)]) => 0xF033 | x << 8,
+        ("LD", [Key("PITCH"), V(x)]) => 0xF03A | x << 8,
+        ("LD", [Key("[I]"), V(x)]) => 0xF055 | x << 8,
+        ("LD", [V(x), Key("[I]")]) => 0xF065 | x << 8,
+        ("LD", [Key("R"), V(x)]) => 0xF075 | x << 8,
+        ("LD", [V(x), Key("R")]) => 0xF085 | x << 8,
+        ("DW", [Num(w)]) => limit(*w, 0xFFFF)?,
+        ("DB", [Num(b)]) => {
+            rom.push(byte(*b)? as u8);
+            return Ok(());
+        }
+        _ => bail!("invalid instruction"),
+    };
+    rom.extend_from_slice(&word.to_be_bytes());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{disasm::disassemble, Emu};
+
+    #[test]
+    fn test_assemble() -> anyhow::Result<()> {
+        let rom = assemble(
+            "
+            ; Sum 1 to 5 into V1
+                LD V0, 5
+            loop:
+                ADD V1, V0
+                ADD V0, 0xFF
+                SE v0, 0
+                JP loop
+            end: JP end
+            ",
+        )?;
+        assert_eq!(
+            rom,
+            [0x60, 0x05, 0x81, 0x04, 0x70, 0xFF, 0x30, 0x00, 0x12, 0x02, 0x12, 0x0A]
+        );
+        let mut emu = Emu::new();
+        emu.load(&rom)?;
+        emu.run_with(|_| true)?;
+        assert_eq!(emu.get_reg(1), 15);
+        Ok(())
+    }
+
+    #[test]
+    fn test_disassembly_round_trip() -> anyhow::Result<()> {
+        let roms: [&[u8]; 3] = [
+            include_bytes!("../../roms/PONG2"),
+            include_bytes!("../../roms/15PUZZLE"),
+            &[
+                0xF0, 0x00, 0x12, 0x34, 0xF2, 0x01, 0xF0, 0x02, 0x5A, 0xB1, 0xFF,
+            ],
+        ];
+        for rom in roms {
+            let src = disassemble(rom)
+                .into_iter()
+                .map(|(_, text)| text)
+                .collect::<Vec<_>>()
+                .join("\n");
+            assert_eq!(assemble(&src)?, rom);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = |src| assemble(src).unwrap_err().root_cause().to_string();
+        assert_eq!(err("JP nowhere"), "unknown label nowhere");
+        assert_eq!(err("LD V0, 0x100"), "0x100 is larger than 0xFF");
+        assert_eq!(err("DRW V0, V1"), "invalid instruction");
+        assert_eq!(err("a: CLS\na: CLS"), "duplicate label a");
+        assert_eq!(err("1a: CLS"), "invalid label \"1a\"");
+        assert_eq!(assemble("CLS\nFOO").unwrap_err().to_string(), "line 2: FOO");
+    }
+}
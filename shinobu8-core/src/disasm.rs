@@ -0,0 +1,149 @@
+use crate::{Instruction, START_ADDR};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+/// Disassembles a ROM image loaded at the program start address into
+/// `(address, mnemonic)` pairs.
+pub fn disassemble(bytes: &[u8]) -> Vec<(u16, String)> {
+    disassemble_at(bytes, START_ADDR)
+}
+
+/// Like [`disassemble`], for bytes read from memory starting at `start`.
+pub fn disassemble_at(bytes: &[u8], start: u16) -> Vec<(u16, String)> {
+    bytes
+        .chunks(2)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let addr = start.wrapping_add((i * 2) as u16);
+            let text = match *chunk {
+                [high, low] => {
+                    disassemble_instruction(Instruction::from(u16::from_be_bytes([high, low])))
+                }
+                [byte] => format!("DB 0x{:02X}", byte),
+                _ => unreachable!(),
+            };
+            (addr, text)
+        })
+        .collect()
+}
+
+/// Renders a single instruction using the mnemonics from Cowgod's technical reference.
+/// Opcodes that don't decode to a known instruction are rendered as `DW 0xHHHH`.
+pub fn disassemble_instruction(ins: Instruction) -> String {
+    let nnn = ins.nnn();
+    let kk = ins.kk();
+    match ins.decode() {
+        (0, 0, 0xE, 0) => "CLS".to_string(),
+        (0, 0, 0xE, 0xE) => "RET".to_string(),
+        (0, 0, 0xC, n) => format!("SCD {}", n),
+        (0, 0, 0xF, 0xB) => "SCR".to_string(),
+        (0, 0, 0xF, 0xC) => "SCL".to_string(),
+        (0, 0, 0xF, 0xD) => "EXIT".to_string(),
+        (0, 0, 0xF, 0xE) => "LOW".to_string(),
+        (0, 0, 0xF, 0xF) => "HIGH".to_string(),
+        (0, _, _, _) => format!("SYS 0x{:03X}", nnn),
+        (1, _, _, _) => format!("JP 0x{:03X}", nnn),
+        (2, _, _, _) => format!("CALL 0x{:03X}", nnn),
+        (3, x, _, _) => format!("SE V{:X}, 0x{:02X}", x, kk),
+        (4, x, _, _) => format!("SNE V{:X}, 0x{:02X}", x, kk),
+        (5, x, y, 0) => format!("SE V{:X}, V{:X}", x, y),
+        (6, x, _, _) => format!("LD V{:X}, 0x{:02X}", x, kk),
+        (7, x, _, _) => format!("ADD V{:X}, 0x{:02X}", x, kk),
+        (8, x, y, 0) => format!("LD V{:X}, V{:X}", x, y),
+        (8, x, y, 1) => format!("OR V{:X}, V{:X}", x, y),
+        (8, x, y, 2) => format!("AND V{:X}, V{:X}", x, y),
+        (8, x, y, 3) => format!("XOR V{:X}, V{:X}", x, y),
+        (8, x, y, 4) => format!("ADD V{:X}, V{:X}", x, y),
+        (8, x, y, 5) => format!("SUB V{:X}, V{:X}", x, y),
+        (8, x, y, 6) => format!("SHR V{:X}, V{:X}", x, y),
+        (8, x, y, 7) => format!("SUBN V{:X}, V{:X}", x, y),
+        (8, x, y, 0xE) => format!("SHL V{:X}, V{:X}", x, y),
+        (9, x, y, 0) => format!("SNE V{:X}, V{:X}", x, y),
+        (0xA, _, _, _) => format!("LD I, 0x{:03X}", nnn),
+        (0xB, _, _, _) => format!("JP V0, 0x{:03X}", nnn),
+        (0xC, x, _, _) => format!("RND V{:X}, 0x{:02X}", x, kk),
+        (0xD, x, y, n) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        (0xE, x, 9, 0xE) => format!("SKP V{:X}", x),
+        (0xE, x, 0xA, 1) => format!("SKNP V{:X}", x),
+        (0xF, 0, 0, 0) => "LD I, LONG".to_string(),
+        (0xF, n, 0, 1) => format!("PLANE {}", n),
+        (0xF, 0, 0, 2) => "LD AUDIO, [I]".to_string(),
+        (0xF, x, 0, 7) => format!("LD V{:X}, DT", x),
+        (0xF, x, 0, 0xA) => format!("LD V{:X}, K", x),
+        (0xF, x, 1, 5) => format!("LD DT, V{:X}", x),
+        (0xF, x, 1, 8) => format!("LD ST, V{:X}", x),
+        (0xF, x, 1, 0xE) => format!("ADD I, V{:X}", x),
+        (0xF, x, 2, 9) => format!("LD F, V{:X}", x),
+        (0xF, x, 3, 0) => format!("LD HF, V{:X}", x),
+        (0xF, x, 3, 3) => format!("LD B, V{:X}", x),
+        (0xF, x, 3, 0xA) => format!("LD PITCH, V{:X}", x),
+        (0xF, x, 5, 5) => format!("LD [I], V{:X}", x),
+        (0xF, x, 6, 5) => format!("LD V{:X}, [I]", x),
+        (0xF, x, 7, 5) => format!("LD R, V{:X}", x),
+        (0xF, x, 8, 5) => format!("LD V{:X}, R", x),
+        _ => format!("DW 0x{:04X}", ins.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_disassemble() {
+        let bytes = [
+            0x60, 0x0A, // LD V0, 0x0A
+            0x81, 0x24, // ADD V1, V2
+            0xA2, 0x10, // LD I, 0x210
+            0xD0, 0x15, // DRW V0, V1, 5
+            0x22, 0x0C, // CALL 0x20C
+            0x12, 0x00, // JP 0x200
+            0x00, 0xEE, // RET
+            0x5A, 0xB1, // invalid
+            0xFF,
+        ];
+        assert_eq!(
+            disassemble(&bytes),
+            vec![
+                (0x200, "LD V0, 0x0A".to_string()),
+                (0x202, "ADD V1, V2".to_string()),
+                (0x204, "LD I, 0x210".to_string()),
+                (0x206, "DRW V0, V1, 5".to_string()),
+                (0x208, "CALL 0x20C".to_string()),
+                (0x20A, "JP 0x200".to_string()),
+                (0x20C, "RET".to_string()),
+                (0x20E, "DW 0x5AB1".to_string()),
+                (0x210, "DB 0xFF".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_disassemble_at() {
+        assert_eq!(
+            disassemble_at(&[0x00, 0xE0, 0x13], 0x301),
+            vec![(0x301, "CLS".to_string()), (0x303, "DB 0x13".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_disassemble_instruction() {
+        assert_eq!(
+            disassemble_instruction(Instruction::from(0xF265)),
+            "LD V2, [I]"
+        );
+        assert_eq!(disassemble_instruction(Instruction::from(0xE39E)), "SKP V3");
+        assert_eq!(
+            disassemble_instruction(Instruction::from(0xF201)),
+            "PLANE 2"
+        );
+        assert_eq!(
+            disassemble_instruction(Instruction::from(0xE3FF)),
+            "DW 0xE3FF"
+        );
+    }
+}
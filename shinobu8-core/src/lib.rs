@@ -1,7 +1,335 @@
-use std::{fmt::Debug, ops::Add, sync::Mutex};
+// Without the default `std` feature only `core` and `alloc` are used, so the emulator
+// can run on targets like microcontrollers. File loading and the thread RNG need `std`.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+#[cfg(test)]
+extern crate std;
+
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    format,
+    string::String,
+    vec,
+    vec::Vec,
+};
+use core::{fmt::Debug, ops::Range};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+#[cfg(feature = "std")]
+use std::{
+    io::Read,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "std")]
+pub mod asm;
+pub mod disasm;
+mod variant;
+
+pub use variant::{detect_variant, Variant};
 
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
+// SUPER-CHIP high resolution mode.
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+
+/// Behaviors that differ between CHIP-8 implementations. The default matches
+/// the original COSMAC VIP interpreter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8XY6`/`8XYE` shift `Vy` into `Vx` instead of shifting `Vx` in place.
+    pub shift_uses_vy: bool,
+    /// `FX55`/`FX65` leave `I` pointing past the last register accessed, adding
+    /// `X + 1` like the COSMAC VIP. `I` is unchanged without this quirk.
+    pub load_store_increments_i: bool,
+    /// `BNNN` jumps to `XNN + VX` instead of `NNN + V0`.
+    pub jump_uses_vx: bool,
+    /// Sprites crossing the screen edge wrap around instead of being clipped.
+    pub sprite_wrapping: bool,
+    /// `FX1E` sets `VF` to 1 when `I` goes past `0x0FFF`, and to 0 otherwise,
+    /// as the Amiga interpreter did.
+    pub i_overflow_sets_vf: bool,
+    /// Fetching an instruction from an odd address is an error instead of reading
+    /// the two bytes there, for catching ROMs that go astray.
+    pub strict_alignment: bool,
+    /// `DXYN` waits for the next vertical blank like the COSMAC VIP, so at most one
+    /// sprite is drawn per frame. See [`Emu::is_waiting_for_vblank`].
+    pub display_wait: bool,
+    /// Opcodes that don't decode to an instruction are skipped instead of failing
+    /// with [`EmuError::UnknownInstruction`], for ROMs that run into data. They still
+    /// reach the trace hook, see [`Emu::set_trace_hook`].
+    pub skip_unknown_opcodes: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            sprite_wrapping: false,
+            i_overflow_sets_vf: false,
+            strict_alignment: false,
+            display_wait: false,
+            skip_unknown_opcodes: false,
+        }
+    }
+}
+
+/// A snapshot of the machine state, see [`Emu::save_state`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EmuState {
+    pc: u16,
+    sp: u8,
+    r_i: u16,
+    regs: [u8; 16],
+    stack: [u16; 16],
+    ram: Vec<u8>,
+    keys: [bool; 16],
+    display: Vec<bool>,
+    display2: Vec<bool>,
+    planes: u8,
+    hires: bool,
+    dt: u8,
+    st: u8,
+}
+
+/// What happened during a [`Emu::run_frame`], so frontends don't have to poll
+/// several getters after each frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameReport {
+    /// Instructions executed.
+    pub steps_run: u64,
+    /// A sprite was drawn with `DXYN`.
+    pub drew: bool,
+    /// The screen was cleared with `00E0`.
+    pub cleared: bool,
+    /// The sound timer started, turning the buzzer on.
+    pub beep_started: bool,
+    /// The program is halted at the end of the frame.
+    pub halted: bool,
+}
+
+/// Result of [`Emu::step_debug`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// An instruction was executed.
+    Normal,
+    /// Execution stopped before the instruction at this address.
+    HitBreakpoint(u16),
+    /// The program halted or the emulator was asked to quit, nothing was executed.
+    Halted,
+}
+
+/// Errors raised while loading or executing programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmuError {
+    /// The opcode doesn't decode to any supported instruction.
+    UnknownInstruction(u16),
+    /// A `5XYN`, `8XYN` or `9XYN` opcode whose last nibble selects no operation,
+    /// e.g. `5XY1`. Usually a typo in hand-assembled code.
+    InvalidOperandNibble(u16),
+    /// `2NNN` at this address with all 16 stack slots in use.
+    StackOverflow(u16),
+    /// `00EE` at this address with an empty stack.
+    StackUnderflow(u16),
+    /// A read from an address outside of memory.
+    OutOfBoundsRead(usize),
+    /// A write to an address outside of memory.
+    OutOfBoundsWrite(usize),
+    /// The ROM doesn't fit between the start address and the end of memory.
+    RomTooLarge { size: usize, max: usize },
+    /// The font doesn't fit below the start address.
+    FontTooLarge { size: usize, max: usize },
+    /// The start address overlaps the font or lies outside of memory.
+    InvalidStartAddress(u16),
+    /// A snapshot whose memory or display size doesn't match this machine, or
+    /// whose stack pointer or program counter is out of range.
+    InvalidState,
+    /// An out of range address, or an odd one under [`Quirks::strict_alignment`],
+    /// passed to [`Emu::set_pc`].
+    InvalidPc(u16),
+    /// An instruction fetch from this odd address under [`Quirks::strict_alignment`].
+    UnalignedPc(u16),
+    /// A memory size passed to [`Emu::with_ram_size`] outside of 4 KB to 64 KB.
+    InvalidRamSize(usize),
+}
+
+impl core::fmt::Display for EmuError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            EmuError::UnknownInstruction(op) => write!(f, "Unknown instruction: {:#06x}", op),
+            EmuError::InvalidOperandNibble(op) => {
+                write!(f, "Invalid last nibble in instruction: {:#06x}", op)
+            }
+            EmuError::StackOverflow(pc) => write!(f, "Stack overflow at {:#05x}", pc),
+            EmuError::StackUnderflow(pc) => write!(f, "Stack underflow at {:#05x}", pc),
+            EmuError::OutOfBoundsRead(addr) => {
+                write!(f, "Memory read out of bounds: {:#06x}", addr)
+            }
+            EmuError::OutOfBoundsWrite(addr) => {
+                write!(f, "Memory write out of bounds: {:#06x}", addr)
+            }
+            EmuError::RomTooLarge { size, max } => {
+                write!(f, "ROM too large: {} bytes, max {}", size, max)
+            }
+            EmuError::FontTooLarge { size, max } => {
+                write!(f, "Font too large: {} bytes, max {}", size, max)
+            }
+            EmuError::InvalidStartAddress(addr) => {
+                write!(f, "Invalid start address: {:#05x}", addr)
+            }
+            EmuError::InvalidState => {
+                write!(f, "Invalid state: mismatched memory or display size")
+            }
+            EmuError::InvalidPc(addr) => write!(f, "Invalid program counter: {:#05x}", addr),
+            EmuError::UnalignedPc(addr) => write!(f, "PC is not aligned: {:#05x}", addr),
+            EmuError::InvalidRamSize(size) => write!(f, "Invalid memory size: {} bytes", size),
+        }
+    }
+}
+
+impl core::error::Error for EmuError {}
+
+/// A keypad change recorded by [`Emu::start_recording`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    /// Value of [`Emu::get_steps`] when the key changed.
+    pub step: u64,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+/// Source of the random bytes used by `CXNN`.
+pub trait RandomSource {
+    fn next_u8(&mut self) -> u8;
+
+    /// A copy continuing the same sequence, used by [`Emu::clone`]. Sources that
+    /// can't be copied return `None`, and the clone gets the default source.
+    fn clone_box(&self) -> Option<Box<dyn RandomSource>> {
+        None
+    }
+}
+
+// The default source, backed by the thread-local RNG.
+#[cfg(feature = "std")]
+struct ThreadRandom;
+
+#[cfg(feature = "std")]
+impl RandomSource for ThreadRandom {
+    fn next_u8(&mut self) -> u8 {
+        rand::random()
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RandomSource>> {
+        Some(Box::new(ThreadRandom))
+    }
+}
+
+impl RandomSource for StdRng {
+    fn next_u8(&mut self) -> u8 {
+        self.gen()
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RandomSource>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+// Cycles through a fixed list of bytes, see `Emu::with_fixed_random`.
+#[derive(Clone)]
+struct FixedRandom {
+    bytes: Vec<u8>,
+    next: usize,
+}
+
+impl RandomSource for FixedRandom {
+    fn next_u8(&mut self) -> u8 {
+        let Some(&byte) = self.bytes.get(self.next) else {
+            return 0;
+        };
+        self.next = (self.next + 1) % self.bytes.len();
+        byte
+    }
+
+    fn clone_box(&self) -> Option<Box<dyn RandomSource>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(feature = "std")]
+fn default_rng() -> Box<dyn RandomSource> {
+    Box::new(ThreadRandom)
+}
+
+// There is no entropy source without `std`, use `Emu::seed_rng` or `Emu::with_rng`.
+#[cfg(not(feature = "std"))]
+fn default_rng() -> Box<dyn RandomSource> {
+    Box::new(StdRng::seed_from_u64(0))
+}
+
+/// Audio output driven by the sound timer.
+pub trait Buzzer {
+    /// Called whenever the sound timer transitions between zero and non-zero.
+    fn set_playing(&mut self, on: bool);
+
+    /// Called when an XO-CHIP program changes the waveform with `F002` or `FX3A`.
+    /// Buzzers that only play a fixed tone can ignore it.
+    #[cfg(feature = "xo_chip")]
+    fn set_pattern(&mut self, _pattern: &AudioPattern) {}
+}
+
+/// The XO-CHIP audio waveform: 128 one-bit samples, most significant bit first,
+/// looped at a rate set by the pitch register.
+#[cfg(feature = "xo_chip")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioPattern {
+    pub samples: [u8; 16],
+    pub pitch: u8,
+}
+
+#[cfg(feature = "xo_chip")]
+impl AudioPattern {
+    /// Playback rate in samples per second, `4000 * 2^((pitch - 64) / 48)`.
+    #[cfg(feature = "std")]
+    pub fn rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+}
+
+#[cfg(feature = "xo_chip")]
+impl Default for AudioPattern {
+    fn default() -> Self {
+        AudioPattern {
+            samples: [0; 16],
+            // 4000 Hz
+            pitch: 64,
+        }
+    }
+}
+
+/// A [`Buzzer`] that produces no sound.
+pub struct NullBuzzer;
+
+impl Buzzer for NullBuzzer {
+    fn set_playing(&mut self, _on: bool) {}
+}
+
+/// A display backend that draws the emulator's screen.
+pub trait Renderer {
+    /// Draws `display`, `width * height` pixels in row-major order.
+    fn present(&mut self, display: &[bool], width: usize, height: usize);
+}
 
 pub struct Emu {
     pc: u16,
@@ -12,40 +340,115 @@ pub struct Emu {
     stack: [u16; 16],
     ram: Ram,
     keys: [bool; 16],
-    display: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+    // XO-CHIP bit planes, each sized for hires mode. Only the first `width * height`
+    // pixels are used in lores mode.
+    display: [[bool; HIRES_WIDTH * HIRES_HEIGHT]; 2],
+    // Bitmask of the planes drawn, cleared and scrolled, selected by `FN01`.
+    planes: u8,
+    // Pixels changed since the last `clear_dirty`, indexed like `display`.
+    dirty: [bool; HIRES_WIDTH * HIRES_HEIGHT],
+    // Whether the screen was wiped since the last `clear_dirty`.
+    display_cleared: bool,
+    // Per-pixel brightness simulating phosphor persistence, indexed like `display`,
+    // updated each frame once `fade_decay` is non-zero.
+    brightness: [u8; HIRES_WIDTH * HIRES_HEIGHT],
+    fade_decay: u8,
+    hires: bool,
     dt: u8,
     st: u8,
+    buzzer: Box<dyn Buzzer>,
+    playing: bool,
+    #[cfg(feature = "xo_chip")]
+    audio: AudioPattern,
+    // SUPER-CHIP RPL user flags, kept across resets like the HP-48's.
+    flags: [u8; 8],
+    cycles_per_frame: u32,
+    steps: u64,
+    // Machine cycles charged by `Instruction::cycles`, for the last step and in total.
+    last_cycles: u32,
+    total_cycles: u64,
+    // Draws that turned a pixel off, since the last reset.
+    collisions: u64,
+    quirks: Quirks,
+    breakpoints: BTreeSet<u16>,
+    // Breakpoint that was just reported, so the next debug step runs past it.
+    break_pc: Option<u16>,
+    trace_hook: Option<Box<dyn FnMut(u16, Instruction)>>,
+    // Executed instruction counts by category, once profiling is enabled.
+    profile: Option<BTreeMap<&'static str, u64>>,
+    rng: Box<dyn RandomSource>,
+    font: Vec<u8>,
+    start_addr: u16,
+    // Hash and size of the last ROM passed to `load`, see `Emu::rom_hash`.
+    rom_hash: u64,
+    rom_len: usize,
+    recording: Option<Vec<InputEvent>>,
+    playback: VecDeque<InputEvent>,
+    // Snapshots taken at the start of each frame, oldest first, at most `rewind_depth` long.
+    rewind: VecDeque<EmuState>,
+    rewind_depth: usize,
+    paused: bool,
+    halted: bool,
+    // Set by DXYN under the display wait quirk, cleared by the next timer tick.
+    vblank_wait: bool,
+    // Events of the frame in progress, returned by `run_frame`.
+    frame: FrameReport,
 
-    quit: Mutex<bool>,
+    quit: bool,
     _priv: (),
 }
 
-struct Ram([u8; 4096]);
+#[derive(Clone)]
+struct Ram(Vec<u8>);
 
 impl Ram {
-    pub fn new() -> Self {
-        Self([0; 4096])
-    }
-
-    pub fn load(&mut self, data: &[u8]) {
-        let start = START_ADDR as usize;
-        let end = start + data.len();
-        self.0[start..end].copy_from_slice(data);
-        self.0[..FONT_SET.len()].copy_from_slice(&FONT_SET);
+    pub fn new(size: usize) -> Self {
+        Self(vec![0; size])
     }
 
-    pub fn read(&self, addr: usize) -> u8 {
-        self.0[addr]
+    pub fn load(&mut self, start: u16, data: &[u8], font: &[u8]) -> Result<(), EmuError> {
+        let start = start as usize;
+        let max = self.0.len() - start;
+        if data.len() > max {
+            return Err(EmuError::RomTooLarge {
+                size: data.len(),
+                max,
+            });
+        }
+        self.0[start..start + data.len()].copy_from_slice(data);
+        self.0[BIG_FONT_ADDR..BIG_FONT_END].copy_from_slice(&BIG_FONT_SET);
+        self.0[..font.len()].copy_from_slice(font);
+        Ok(())
     }
 
     pub fn store(&mut self, addr: usize, data: u8) {
         self.0[addr] = data;
     }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn try_read(&self, addr: usize) -> Result<u8, EmuError> {
+        self.0
+            .get(addr)
+            .copied()
+            .ok_or(EmuError::OutOfBoundsRead(addr))
+    }
+
+    pub fn try_store(&mut self, addr: usize, data: u8) -> Result<(), EmuError> {
+        let byte = self
+            .0
+            .get_mut(addr)
+            .ok_or(EmuError::OutOfBoundsWrite(addr))?;
+        *byte = data;
+        Ok(())
+    }
 }
 
 impl Default for Ram {
     fn default() -> Self {
-        Self::new()
+        Self::new(RAM_SIZE)
     }
 }
 
@@ -68,371 +471,3563 @@ const FONT_SET: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
+// SUPER-CHIP 8x10 digits for FX30, stored right after the 80-byte small font.
+const BIG_FONT_ADDR: usize = 0x50;
+const BIG_FONT_SET: [u8; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+const BIG_FONT_END: usize = BIG_FONT_ADDR + BIG_FONT_SET.len();
+
 const START_ADDR: u16 = 0x200;
+// The standard 4 KB, XO-CHIP machines can address up to 64 KB.
+const RAM_SIZE: usize = 0x1000;
+const MAX_RAM_SIZE: usize = 0x10000;
+
+/// Frames per second the timers and [`Emu::run_frame`] are designed around.
+pub const FRAME_RATE: u32 = 60;
+const DEFAULT_CYCLES_PER_FRAME: u32 = 10;
 
 impl Emu {
     pub fn new() -> Self {
         Self::default()
     }
 
-    pub fn load(&mut self, rom: &[u8]) {
-        self.ram.load(rom);
+    pub fn with_quirks(quirks: Quirks) -> Self {
+        Self {
+            quirks,
+            ..Self::default()
+        }
     }
 
-    pub fn get_diaplay(&self) -> &[bool; 64 * 32] {
-        &self.display
+    /// Creates an emulator drawing `CXNN` random bytes from `rng`.
+    pub fn with_rng(rng: impl RandomSource + 'static) -> Self {
+        Self {
+            rng: Box::new(rng),
+            ..Self::default()
+        }
     }
 
-    pub fn key_press(&mut self, key: u8) {
-        self.keys[key as usize] = true;
+    /// Creates an emulator whose `CXNN` draws `bytes` in order, starting over after
+    /// the last one, for fully reproducible tests. No bytes always gives 0.
+    pub fn with_fixed_random(bytes: Vec<u8>) -> Self {
+        Self::with_rng(FixedRandom { bytes, next: 0 })
     }
 
-    // Reset all keys, in linux server, can't get key release event,
-    // so we need to reset all keys before call key_press.
-    pub fn reset_keypad(&mut self) {
-        for i in 0..16 {
-            self.keys[i] = false;
+    /// Replaces the random source with a deterministic RNG seeded from `seed`.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = Box::new(StdRng::seed_from_u64(seed));
+    }
+
+    /// Creates an emulator with `rom` loaded, see [`Emu::load`].
+    pub fn from_rom(rom: &[u8]) -> Result<Self, EmuError> {
+        let mut emu = Self::new();
+        emu.load(rom)?;
+        Ok(emu)
+    }
+
+    /// Creates an emulator with the ROM in a file loaded, see [`Emu::load_file`].
+    #[cfg(feature = "std")]
+    pub fn from_rom_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let mut emu = Self::new();
+        emu.load_file(path)?;
+        Ok(emu)
+    }
+
+    /// Creates an emulator with `size` bytes of memory, between the standard 4 KB
+    /// and the 64 KB XO-CHIP programs can reach with `F000 NNNN`.
+    pub fn with_ram_size(size: usize) -> Result<Self, EmuError> {
+        if !(RAM_SIZE..=MAX_RAM_SIZE).contains(&size) {
+            return Err(EmuError::InvalidRamSize(size));
         }
+        Ok(Self {
+            ram: Ram::new(size),
+            ..Self::default()
+        })
     }
 
-    pub fn key_release(&mut self, key: u8) {
-        self.keys[key as usize] = false;
+    /// Creates an emulator that loads programs and starts executing at `addr`
+    /// instead of `0x200`, e.g. `0x600` for ETI-660 programs.
+    pub fn with_start_addr(addr: u16) -> Result<Self, EmuError> {
+        let mut emu = Self::default();
+        emu.set_start_addr(addr)?;
+        Ok(emu)
     }
 
-    pub fn quit(&mut self) {
-        let mut quit = self.quit.lock().unwrap();
-        *quit = true;
+    /// Starts configuring an emulator setting by setting, see [`EmuBuilder`].
+    pub fn builder() -> EmuBuilder {
+        EmuBuilder::default()
     }
 
-    pub fn cycle(&mut self) -> anyhow::Result<()> {
-        for _ in 0..8 {
-            self.step()?;
+    fn set_start_addr(&mut self, addr: u16) -> Result<(), EmuError> {
+        if addr as usize >= self.ram.len() || (addr as usize) < BIG_FONT_END {
+            return Err(EmuError::InvalidStartAddress(addr));
         }
-        self.update_times();
+        self.pc = addr;
+        self.start_addr = addr;
         Ok(())
     }
 
-    pub fn step(&mut self) -> anyhow::Result<()> {
-        let instr = self.fetch();
-        self.execute(instr)?;
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Loads a ROM at the program start address and installs the font set.
+    pub fn load(&mut self, rom: &[u8]) -> Result<(), EmuError> {
+        self.ram.load(self.start_addr, rom, &self.font)?;
+        self.rom_hash = fnv1a(rom);
+        self.rom_len = rom.len();
         Ok(())
     }
 
-    fn reg(&self, i: u8) -> u8 {
-        assert!(i < 16, "Invalid register index");
-        self.regs[i as usize]
+    /// A stable 64-bit FNV-1a hash of the loaded ROM, for telling which game a save
+    /// state or bug report belongs to. It is taken at load time, so programs writing
+    /// over their own code don't change it.
+    pub fn rom_hash(&self) -> u64 {
+        self.rom_hash
     }
 
-    fn jump_next(&mut self) {
-        self.pc += 2;
+    /// Size in bytes of the loaded ROM.
+    pub fn rom_len(&self) -> usize {
+        self.rom_len
     }
 
-    fn update_times(&mut self) {
-        if self.dt > 0 {
-            self.dt -= 1;
+    /// Disassembles the loaded ROM as it is now in memory, see [`disasm::disassemble_at`].
+    pub fn disassemble_rom(&self) -> Vec<(u16, String)> {
+        let start = self.start_addr as usize;
+        disasm::disassemble_at(self.mem_slice(start..start + self.rom_len), self.start_addr)
+    }
+
+    /// Copies `data` to memory at `addr`, e.g. an overlay of a multi-part program.
+    /// Unlike [`Emu::load`], the font and the rest of memory are left alone. Fails
+    /// with [`EmuError::OutOfBoundsWrite`] if `addr` lies past the end of memory.
+    pub fn load_at(&mut self, addr: u16, data: &[u8]) -> Result<(), EmuError> {
+        let addr = addr as usize;
+        if addr > self.ram.len() {
+            return Err(EmuError::OutOfBoundsWrite(addr));
         }
-        if self.st > 0 {
-            self.st -= 1;
+        let max = self.ram.len() - addr;
+        if data.len() > max {
+            return Err(EmuError::RomTooLarge {
+                size: data.len(),
+                max,
+            });
         }
+        self.ram.0[addr..addr + data.len()].copy_from_slice(data);
+        Ok(())
     }
 
-    fn execute(&mut self, ins: Instruction) -> anyhow::Result<()> {
-        // println!("next: {:?}", ins);
+    /// Reads a ROM from a file and loads it, see [`Emu::load`].
+    #[cfg(feature = "std")]
+    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> anyhow::Result<()> {
+        let rom = std::fs::read(path)?;
+        Ok(self.load(&rom)?)
+    }
 
-        match ins.decode() {
-            (0, 0, 0, 0) => {}
-            (0, 0, 0xE, 0) => {
-                // Clear the display.
-                self.display = [false; 64 * 32];
-            }
-            (0, 0, 0xE, 0xE) => {
-                // Return from a subroutine.
-                // The interpreter sets the program counter to the address at the top of the stack, then subtracts 1 from the stack pointer.
-                self.sp -= 1;
-                self.pc = self.stack[self.sp as usize];
-            }
-            (1, _, _, _) => {
-                self.pc = ins.nnn();
-            }
-            (2, _, _, _) => {
-                self.stack[self.sp as usize] = self.pc;
-                self.sp += 1;
-                self.pc = ins.nnn();
-            }
-            (3, x, _, _) => {
-                if self.reg(x) == ins.kk() {
-                    self.jump_next();
-                }
-            }
-            (4, x, _, _) => {
-                if self.reg(x) != ins.kk() {
-                    self.jump_next();
-                }
-            }
-            (5, x, y, 0) => {
-                if self.reg(x) == self.reg(y) {
-                    self.jump_next();
+    /// Reads a ROM to the end of `r` and loads it, see [`Emu::load`].
+    #[cfg(feature = "std")]
+    pub fn load_reader<R: Read>(&mut self, mut r: R) -> anyhow::Result<()> {
+        let mut rom = Vec::new();
+        r.read_to_end(&mut rom)?;
+        Ok(self.load(&rom)?)
+    }
+
+    /// Installs a custom font at the bottom of memory, replacing the default one
+    /// for this and any later [`Emu::load`].
+    ///
+    /// `FX29` addresses glyphs as `Vx * 5`, so the font must keep the 5-byte
+    /// glyph layout of the default font to be usable by ROMs. Fonts longer than
+    /// 80 bytes overwrite the SUPER-CHIP big font used by `FX30`.
+    pub fn set_font(&mut self, font: &[u8]) -> Result<(), EmuError> {
+        if font.len() > self.start_addr as usize {
+            return Err(EmuError::FontTooLarge {
+                size: font.len(),
+                max: self.start_addr as usize,
+            });
+        }
+        self.font = font.to_vec();
+        for (addr, byte) in font.iter().enumerate() {
+            self.ram.store(addr, *byte);
+        }
+        Ok(())
+    }
+
+    /// Resets the CPU, timers, keypad and display to their power-on state.
+    /// Memory is left untouched so the loaded ROM can be restarted.
+    pub fn reset(&mut self) {
+        self.pc = self.start_addr;
+        self.sp = 0;
+        self.r_i = 0;
+        self.regs = [0; 16];
+        self.stack = [0; 16];
+        self.keys = [false; 16];
+        self.display = [[false; HIRES_WIDTH * HIRES_HEIGHT]; 2];
+        self.planes = 1;
+        self.dirty = [true; HIRES_WIDTH * HIRES_HEIGHT];
+        self.display_cleared = true;
+        self.hires = false;
+        self.dt = 0;
+        self.st = 0;
+        #[cfg(feature = "xo_chip")]
+        {
+            self.audio = AudioPattern::default();
+        }
+        self.steps = 0;
+        self.last_cycles = 0;
+        self.total_cycles = 0;
+        self.collisions = 0;
+        self.break_pc = None;
+        self.halted = false;
+        self.vblank_wait = false;
+        self.quit = false;
+        self.update_buzzer();
+    }
+
+    /// Resets the machine and replaces the memory contents with a fresh ROM.
+    /// If the ROM doesn't fit, the machine is left untouched.
+    pub fn reset_and_reload(&mut self, rom: &[u8]) -> Result<(), EmuError> {
+        let mut ram = Ram::new(self.ram.len());
+        ram.load(self.start_addr, rom, &self.font)?;
+        self.reset();
+        self.ram = ram;
+        self.rom_hash = fnv1a(rom);
+        self.rom_len = rom.len();
+        Ok(())
+    }
+
+    /// The display in row-major order, `width * height` pixels as reported by
+    /// [`Emu::display_dimensions`].
+    pub fn get_display(&self) -> &[bool] {
+        let (width, height) = self.display_dimensions();
+        &self.display[0][..width * height]
+    }
+
+    /// XO-CHIP bit plane `plane` (0 or 1) in the same layout as [`Emu::get_display`],
+    /// which is plane 0.
+    pub fn get_plane(&self, plane: usize) -> &[bool] {
+        assert!(plane < 2, "Invalid plane index");
+        let (width, height) = self.display_dimensions();
+        &self.display[plane][..width * height]
+    }
+
+    /// The display as 2-bit XO-CHIP colors, bit 0 from plane 0 and bit 1 from plane 1,
+    /// for frontends mapping pixels to a 4-color palette.
+    pub fn get_color_display(&self) -> Vec<u8> {
+        self.get_plane(0)
+            .iter()
+            .zip(self.get_plane(1))
+            .map(|(&lo, &hi)| lo as u8 | (hi as u8) << 1)
+            .collect()
+    }
+
+    /// The 64x32 display packed 8 pixels to a byte, most significant bit first like
+    /// sprite data, row by row. In hires mode every other pixel of every other row
+    /// is taken, see [`Emu::display_packed_hires`] for the full resolution.
+    pub fn display_packed(&self) -> [u8; SCREEN_WIDTH * SCREEN_HEIGHT / 8] {
+        self.pack(SCREEN_WIDTH)
+    }
+
+    /// Like [`Emu::display_packed`] at the 128x64 hires resolution. In lores mode
+    /// every pixel is doubled in both directions.
+    pub fn display_packed_hires(&self) -> [u8; HIRES_WIDTH * HIRES_HEIGHT / 8] {
+        self.pack(HIRES_WIDTH)
+    }
+
+    // Packs the display scaled to `width` pixels across, half or twice the current
+    // width, or the same.
+    fn pack<const N: usize>(&self, width: usize) -> [u8; N] {
+        let (display_width, _) = self.display_dimensions();
+        let mut packed = [0; N];
+        for (i, byte) in packed.iter_mut().enumerate() {
+            for bit in 0..8 {
+                let (x, y) = ((i * 8 + bit) % width, (i * 8 + bit) / width);
+                if self.pixel(x * display_width / width, y * display_width / width) {
+                    *byte |= 0x80 >> bit;
                 }
             }
-            (6, x, _, _) => {
-                self.regs[x as usize] = ins.kk();
-            }
-            (7, x, _, _) => {
-                self.regs[x as usize] = self.reg(x).wrapping_add(ins.kk());
-            }
-            (8, x, y, 0) => {
-                // Vx = Vy.
-                self.regs[x as usize] = self.reg(y);
-            }
-            (8, x, y, 1) => {
-                // Vx |= Vy.
-                self.regs[x as usize] |= self.reg(y);
-            }
-            (8, x, y, 2) => {
-                // Vx &= Vy.
-                self.regs[x as usize] &= self.reg(y);
-            }
-            (8, x, y, 3) => {
-                // Vx ^= Vy.
-                self.regs[x as usize] ^= self.reg(y);
-            }
-            (8, x, y, 4) => {
-                // Vx += Vy.
-                let (sum, overed) = self.reg(x).overflowing_add(self.reg(y));
-                if overed {
-                    self.regs[0xF] = 1;
+        }
+        packed
+    }
+
+    /// Coordinates `(x, y)` of the pixels changed since the last [`Emu::clear_dirty`],
+    /// so frontends can redraw only those.
+    pub fn dirty_pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let (width, height) = self.display_dimensions();
+        self.dirty[..width * height]
+            .iter()
+            .enumerate()
+            .filter(|(_, &dirty)| dirty)
+            .map(move |(i, _)| (i % width, i / width))
+    }
+
+    pub fn clear_dirty(&mut self) {
+        self.dirty = [false; HIRES_WIDTH * HIRES_HEIGHT];
+        self.display_cleared = false;
+    }
+
+    /// Whether `00E0` or [`Emu::reset`] wiped the screen since the last
+    /// [`Emu::clear_dirty`], so frontends can clear everything at once instead of
+    /// going through [`Emu::dirty_pixels`].
+    pub fn display_cleared(&self) -> bool {
+        self.display_cleared
+    }
+
+    /// Compares the display against a `previous` copy of [`Emu::get_display`] and
+    /// returns `(x, y, on)` for every pixel that changed. If the sizes differ, e.g.
+    /// after a resolution switch, every pixel is returned.
+    pub fn display_diff(&self, previous: &[bool]) -> Vec<(usize, usize, bool)> {
+        let (width, _) = self.display_dimensions();
+        let display = self.get_display();
+        let resized = previous.len() != display.len();
+        display
+            .iter()
+            .enumerate()
+            .filter(|&(i, &on)| resized || previous[i] != on)
+            .map(|(i, &on)| (i % width, i / width, on))
+            .collect()
+    }
+
+    #[deprecated(note = "use get_display")]
+    pub fn get_diaplay(&self) -> &[bool] {
+        self.get_display()
+    }
+
+    /// Whether the pixel in column `x` and row `y` is lit, counting from the top
+    /// left. Pixels outside of the current display are unlit.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.display_index(x, y)
+            .is_some_and(|index| self.display[0][index])
+    }
+
+    // Index of pixel (x, y) in the display buffers, `None` outside the current display.
+    fn display_index(&self, x: usize, y: usize) -> Option<usize> {
+        let (width, height) = self.display_dimensions();
+        (x < width && y < height).then_some(y * width + x)
+    }
+
+    /// Current display `(width, height)`, which changes with SUPER-CHIP hires mode.
+    pub fn display_dimensions(&self) -> (usize, usize) {
+        if self.hires {
+            (HIRES_WIDTH, HIRES_HEIGHT)
+        } else {
+            (SCREEN_WIDTH, SCREEN_HEIGHT)
+        }
+    }
+
+    /// Hands the current display to `renderer`.
+    pub fn render_with(&self, renderer: &mut impl Renderer) {
+        let (width, height) = self.display_dimensions();
+        renderer.present(self.get_display(), width, height);
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    /// Renders the display as a binary PPM (P6) image, lit pixels white and unlit black.
+    pub fn to_ppm(&self) -> Vec<u8> {
+        self.to_ppm_scaled(1)
+    }
+
+    /// Like [`Emu::to_ppm`], with every pixel scaled to a `scale`x`scale` square.
+    pub fn to_ppm_scaled(&self, scale: usize) -> Vec<u8> {
+        let scale = scale.max(1);
+        let (width, height) = self.display_dimensions();
+        let display = self.get_display();
+        let mut ppm = format!("P6\n{} {}\n255\n", width * scale, height * scale).into_bytes();
+        for y in 0..height * scale {
+            for x in 0..width * scale {
+                let value = if display[(y / scale) * width + x / scale] {
+                    0xFF
                 } else {
-                    self.regs[0xF] = 0;
-                }
-                self.regs[x as usize] = sum;
-            }
-            (8, x, y, 5) => {
-                // Vx -= Vy.
-                self.regs[x as usize] = self.sub(self.reg(x), self.reg(y));
+                    0x00
+                };
+                ppm.extend_from_slice(&[value; 3]);
             }
-            (8, x, _y, 6) => {
-                // Vx >>= 1.
-                self.regs[0xF] = self.reg(x) & 1;
-                self.regs[x as usize] = self.reg(x) >> 1;
-            }
-            (8, x, y, 7) => {
-                // Vx = Vy - Vx.
-                self.regs[x as usize] = self.sub(self.reg(y), self.reg(x));
-            }
-            (8, x, _y, 0xE) => {
-                // Vx <<= 1.
-                self.regs[0xF] = self.reg(x) >> 7 & 1;
-                self.regs[x as usize] = self.reg(x) << 1;
-            }
-            (9, x, y, 0) => {
-                if self.reg(x) != self.reg(y) {
-                    self.jump_next();
-                }
+        }
+        ppm
+    }
+
+    /// Reads register `Vi`. Panics if `i` is not in `0..16`.
+    pub fn get_reg(&self, i: u8) -> u8 {
+        self.reg(i)
+    }
+
+    /// Writes register `Vi`. Panics if `i` is not in `0..16`.
+    pub fn set_reg(&mut self, i: u8, val: u8) {
+        assert!(i < 16, "Invalid register index");
+        self.regs[i as usize] = val;
+    }
+
+    pub fn get_i(&self) -> u16 {
+        self.r_i
+    }
+
+    pub fn set_i(&mut self, val: u16) {
+        self.r_i = val;
+    }
+
+    pub fn get_pc(&self) -> u16 {
+        self.pc
+    }
+
+    /// Moves execution to `addr`, e.g. to "set next instruction" in a debugger.
+    /// The address must leave room for a whole instruction, and be even under
+    /// [`Quirks::strict_alignment`].
+    pub fn set_pc(&mut self, addr: u16) -> Result<(), EmuError> {
+        let misaligned = self.quirks.strict_alignment && !addr.is_multiple_of(2);
+        if misaligned || addr as usize + 1 >= self.ram.len() {
+            return Err(EmuError::InvalidPc(addr));
+        }
+        self.pc = addr;
+        Ok(())
+    }
+
+    pub fn get_sp(&self) -> u8 {
+        self.sp
+    }
+
+    /// Return addresses of the active subroutine calls, outermost first.
+    pub fn call_stack(&self) -> &[u16] {
+        &self.stack[..self.sp as usize]
+    }
+
+    pub fn get_delay_timer(&self) -> u8 {
+        self.dt
+    }
+
+    pub fn set_delay_timer(&mut self, val: u8) {
+        self.dt = val;
+    }
+
+    pub fn get_sound_timer(&self) -> u8 {
+        self.st
+    }
+
+    /// Sets the sound timer like `FX18`, starting or stopping the buzzer.
+    pub fn set_sound_timer(&mut self, val: u8) {
+        self.st = val;
+        self.update_buzzer();
+    }
+
+    /// The SUPER-CHIP RPL user flags written by `FX75`, e.g. to save them between runs.
+    pub fn get_flags(&self) -> [u8; 8] {
+        self.flags
+    }
+
+    /// Restores RPL user flags saved from [`Emu::get_flags`].
+    pub fn set_flags(&mut self, flags: [u8; 8]) {
+        self.flags = flags;
+    }
+
+    /// Formats the CPU registers and timers in hex, e.g. for logs and crash reports.
+    pub fn dump_state(&self) -> String {
+        let mut out = format!(
+            "PC={:04X} I={:04X} SP={:X} DT={:02X} ST={:02X}",
+            self.pc, self.r_i, self.sp, self.dt, self.st
+        );
+        for (i, reg) in self.regs.iter().enumerate() {
+            let sep = if i % 8 == 0 { '\n' } else { ' ' };
+            out.push_str(&format!("{}V{:X}={:02X}", sep, i, reg));
+        }
+        out
+    }
+
+    /// Draws the display as text, `#` for lit pixels and a space for unlit ones,
+    /// one line per row. Handy for asserting on screen contents in tests.
+    pub fn display_to_string(&self) -> String {
+        let (width, _) = self.display_dimensions();
+        self.get_display()
+            .chunks(width)
+            .map(|row| {
+                row.iter()
+                    .map(|&on| if on { '#' } else { ' ' })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Reads a byte of memory, `None` if `addr` is outside RAM.
+    pub fn read_mem(&self, addr: u16) -> Option<u8> {
+        self.ram.0.get(addr as usize).copied()
+    }
+
+    /// Writes a byte of memory, returns `false` if `addr` is outside RAM. Writes to
+    /// code take effect at the next fetch, instructions are never cached.
+    pub fn write_mem(&mut self, addr: u16, val: u8) -> bool {
+        match self.ram.0.get_mut(addr as usize) {
+            Some(byte) => {
+                *byte = val;
+                true
             }
-            (0xA, _, _, _) => {
-                self.r_i = ins.nnn();
+            None => false,
+        }
+    }
+
+    /// Returns a view of memory, with `range` clamped to the size of RAM.
+    pub fn mem_slice(&self, range: Range<usize>) -> &[u8] {
+        let end = range.end.min(self.ram.len());
+        let start = range.start.min(end);
+        &self.ram.0[start..end]
+    }
+
+    pub fn save_state(&self) -> EmuState {
+        EmuState {
+            pc: self.pc,
+            sp: self.sp,
+            r_i: self.r_i,
+            regs: self.regs,
+            stack: self.stack,
+            ram: self.ram.0.to_vec(),
+            keys: self.keys,
+            display: self.display[0].to_vec(),
+            display2: self.display[1].to_vec(),
+            planes: self.planes,
+            hires: self.hires,
+            dt: self.dt,
+            st: self.st,
+        }
+    }
+
+    /// Restores a snapshot taken by [`Emu::save_state`]. Snapshots from elsewhere,
+    /// e.g. deserialized ones, are checked so that they can't make later steps panic.
+    pub fn load_state(&mut self, state: EmuState) -> Result<(), EmuError> {
+        // I is left unchecked: programs can point it past memory with F000 or FX1E,
+        // and every access through it is bounds checked.
+        if state.ram.len() != self.ram.0.len()
+            || state.display.len() != self.display[0].len()
+            || state.display2.len() != self.display[1].len()
+            || state.sp as usize > state.stack.len()
+            || state.pc as usize >= state.ram.len()
+        {
+            return Err(EmuError::InvalidState);
+        }
+        self.pc = state.pc;
+        self.sp = state.sp;
+        self.r_i = state.r_i;
+        self.regs = state.regs;
+        self.stack = state.stack;
+        self.ram.0.copy_from_slice(&state.ram);
+        self.keys = state.keys;
+        self.display[0].copy_from_slice(&state.display);
+        self.display[1].copy_from_slice(&state.display2);
+        self.planes = state.planes;
+        self.dirty = [true; HIRES_WIDTH * HIRES_HEIGHT];
+        self.hires = state.hires;
+        self.dt = state.dt;
+        self.st = state.st;
+        self.update_buzzer();
+        Ok(())
+    }
+
+    /// Keeps a snapshot of the last `depth` frames run by [`Emu::run_frame`] so they
+    /// can be stepped back through with [`Emu::rewind`]. A depth of 0 disables rewinding.
+    pub fn enable_rewind(&mut self, depth: usize) {
+        self.rewind_depth = depth;
+        while self.rewind.len() > depth {
+            self.rewind.pop_front();
+        }
+    }
+
+    /// Restores the state from before the last recorded frame. Returns `false` if
+    /// there is nothing left to rewind.
+    pub fn rewind(&mut self) -> bool {
+        match self.rewind.pop_back() {
+            Some(state) => self.load_state(state).is_ok(),
+            None => false,
+        }
+    }
+
+    pub fn set_buzzer(&mut self, buzzer: Box<dyn Buzzer>) {
+        self.buzzer = buzzer;
+    }
+
+    /// Marks keypad key `key` (`0x0..=0xF`) as held. Other values are ignored.
+    pub fn key_press(&mut self, key: u8) {
+        self.set_key(key, true);
+    }
+
+    // Reset all keys, in linux server, can't get key release event,
+    // so we need to reset all keys before call key_press.
+    pub fn reset_keypad(&mut self) {
+        for i in 0..16 {
+            self.set_key(i, false);
+        }
+    }
+
+    /// Marks keypad key `key` (`0x0..=0xF`) as released. Other values are ignored.
+    pub fn key_release(&mut self, key: u8) {
+        self.set_key(key, false);
+    }
+
+    /// Whether keypad key `key` is held. Values above `0xF` are never pressed.
+    pub fn is_key_pressed(&self, key: u8) -> bool {
+        self.keys.get(key as usize).copied().unwrap_or(false)
+    }
+
+    /// The keypad keys currently held, in ascending order.
+    pub fn pressed_keys(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..16u8).filter(|&key| self.keys[key as usize])
+    }
+
+    /// Presses `keys`, runs `f` and releases them again, returning what `f` returns.
+    pub fn with_keys_held<R>(&mut self, keys: &[u8], f: impl FnOnce(&mut Emu) -> R) -> R {
+        for &key in keys {
+            self.key_press(key);
+        }
+        let result = f(self);
+        for &key in keys {
+            self.key_release(key);
+        }
+        result
+    }
+
+    fn set_key(&mut self, key: u8, pressed: bool) {
+        let Some(state) = self.keys.get_mut(key as usize) else {
+            return;
+        };
+        if *state == pressed {
+            return;
+        }
+        *state = pressed;
+        if let Some(recording) = self.recording.as_mut() {
+            recording.push(InputEvent {
+                step: self.steps,
+                key,
+                pressed,
+            });
+        }
+    }
+
+    /// Starts logging every keypad change, tagged with the current step count.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    /// Stops recording and returns the events captured since [`Emu::start_recording`].
+    pub fn take_recording(&mut self) -> Vec<InputEvent> {
+        self.recording.take().unwrap_or_default()
+    }
+
+    /// Replays recorded events, each applied right before the step it was recorded at.
+    /// Combined with a seeded RNG this reproduces a run exactly.
+    pub fn play_recording(&mut self, mut events: Vec<InputEvent>) {
+        events.sort_by_key(|e| e.step);
+        self.playback = events.into();
+    }
+
+    fn apply_playback(&mut self) {
+        while let Some(event) = self.playback.front() {
+            if event.step > self.steps {
+                break;
             }
-            (0xB, _, _, _) => {
-                self.pc = ins.nnn() + self.regs[0] as u16;
+            if let Some(state) = self.keys.get_mut(event.key as usize) {
+                *state = event.pressed;
             }
-            (0xC, x, _, _) => {
-                // Vx = random byte AND kk.
-                let random_byte = rand::random::<u8>();
-                self.regs[x as usize] = random_byte & ins.kk();
+            self.playback.pop_front();
+        }
+    }
+
+    pub fn quit(&mut self) {
+        self.quit = true;
+    }
+
+    pub fn cycles_per_frame(&self) -> u32 {
+        self.cycles_per_frame
+    }
+
+    /// Sets how many instructions [`Emu::run_frame`] executes, at least 1.
+    pub fn set_cycles_per_frame(&mut self, cycles: u32) {
+        self.cycles_per_frame = cycles.max(1);
+    }
+
+    /// Sets the CPU speed in instructions per second, rounded to whole frames.
+    pub fn set_clock_hz(&mut self, hz: u32) {
+        self.set_cycles_per_frame(hz / FRAME_RATE);
+    }
+
+    /// Number of instructions executed so far.
+    pub fn get_steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// VIP machine cycles the last executed instruction took, see [`Instruction::cycles`].
+    pub fn last_instruction_cycles(&self) -> u32 {
+        self.last_cycles
+    }
+
+    /// VIP machine cycles taken by all instructions executed so far, for frontends
+    /// pacing by time spent rather than by instruction count.
+    pub fn total_cycles(&self) -> u64 {
+        self.total_cycles
+    }
+
+    /// Number of `DXYN` draws that collided, setting VF to 1, since the last reset,
+    /// e.g. to see how often sprites overlap.
+    pub fn collision_count(&self) -> u64 {
+        self.collisions
+    }
+
+    /// Restarts [`Emu::collision_count`] from zero, e.g. to measure a single level.
+    pub fn reset_collision_count(&mut self) {
+        self.collisions = 0;
+    }
+
+    /// Paused emulators don't advance in [`Emu::run_frame`]; [`Emu::step`] still
+    /// executes instructions so a debugger can single-step.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Whether the program has stopped, either with the SUPER-CHIP `00FD` exit or
+    /// by jumping to its own address, which ROMs commonly use to signal they are
+    /// done. [`Emu::step`] does nothing once halted.
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Whether a `DXYN` under [`Quirks::display_wait`] is waiting for the next
+    /// [`Emu::tick_timers`]. [`Emu::step`] does nothing until then, so hosts
+    /// driving the CPU themselves should end the current frame.
+    pub fn is_waiting_for_vblank(&self) -> bool {
+        self.vblank_wait
+    }
+
+    /// Executes one 60 Hz frame: `cycles_per_frame` instructions followed by a timer tick,
+    /// and reports what happened. Does nothing while paused.
+    pub fn run_frame(&mut self) -> Result<FrameReport, EmuError> {
+        if self.paused {
+            return Ok(FrameReport {
+                halted: self.halted,
+                ..FrameReport::default()
+            });
+        }
+        if self.rewind_depth > 0 {
+            if self.rewind.len() == self.rewind_depth {
+                self.rewind.pop_front();
             }
-            (0xD, x, y, n) => {
-                let start = self.r_i as usize;
-                let mut collision = false;
-                let x = self.reg(x) as usize;
-                let y = self.reg(y) as usize;
-
-                for y_line in 0..n {
-                    let sprite = self.ram.read(start + y_line as usize);
-                    let y = (y + y_line as usize) % 32;
-                    for x_line in 0..8 {
-                        if (sprite & (0b1000_0000 >> x_line)) != 0 {
-                            let x = (x + x_line) % 64;
-                            let index = y * 64 + x;
-                            if self.display[index] {
-                                collision = true;
-                            }
-                            self.display[index] ^= true;
-                        }
-                    }
+            self.rewind.push_back(self.save_state());
+        }
+        self.frame = FrameReport::default();
+        let steps = self.steps;
+        self.cycle()?;
+        self.tick_timers();
+        self.frame.steps_run = self.steps - steps;
+        self.frame.halted = self.halted;
+        if self.fade_decay > 0 {
+            let display = &self.display[0];
+            for (level, &on) in self.brightness.iter_mut().zip(display.iter()) {
+                *level = if on {
+                    u8::MAX
+                } else {
+                    level.saturating_sub(self.fade_decay)
+                };
+            }
+        }
+        Ok(self.frame)
+    }
+
+    /// Starts tracking [`Emu::display_brightness`]: lit pixels are at full
+    /// brightness, and unlit ones lose `decay` each frame, like a CRT's fading
+    /// phosphor. This hides the flicker of sprites erased and redrawn every frame.
+    /// A decay of 0 turns it off.
+    pub fn enable_fade(&mut self, decay: u8) {
+        self.fade_decay = decay;
+        self.brightness = [0; HIRES_WIDTH * HIRES_HEIGHT];
+    }
+
+    /// Brightness of each pixel from 0 to 255, in the same layout as
+    /// [`Emu::get_display`], as of the end of the last frame. All zero unless
+    /// [`Emu::enable_fade`] was called.
+    pub fn display_brightness(&self) -> &[u8] {
+        let (width, height) = self.display_dimensions();
+        &self.brightness[..width * height]
+    }
+
+    /// Runs frames back to back, handing the display to `on_frame` after each one,
+    /// until the callback returns `false`, [`Emu::quit`] is called or the program halts.
+    pub fn run_with<F: FnMut(&[bool]) -> bool>(&mut self, mut on_frame: F) -> Result<(), EmuError> {
+        while !self.quit && !self.halted {
+            self.run_frame()?;
+            if !on_frame(self.get_display()) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`Emu::run_with`], but sleeps between frames to keep to [`FRAME_RATE`],
+    /// so games run at their real speed without a frontend doing the pacing.
+    /// Frames that fall behind are not made up for.
+    #[cfg(feature = "std")]
+    pub fn run_paced<F: FnMut(&[bool]) -> bool>(
+        &mut self,
+        mut on_frame: F,
+    ) -> Result<(), EmuError> {
+        let interval = Duration::from_nanos(1_000_000_000 / FRAME_RATE as u64);
+        let mut next_frame = Instant::now();
+        self.run_with(|display| {
+            if !on_frame(display) {
+                return false;
+            }
+            let now = Instant::now();
+            next_frame = (next_frame + interval).max(now);
+            std::thread::sleep(next_frame - now);
+            true
+        })
+    }
+
+    /// Executes `cycles_per_frame` instructions. Timers are not touched here since
+    /// the CPU runs much faster than 60 Hz, callers driving the emulator themselves
+    /// must call [`Emu::tick_timers`] at 60 Hz.
+    /// Stops early if a draw waits for the vertical blank.
+    pub fn cycle(&mut self) -> Result<(), EmuError> {
+        for _ in 0..self.cycles_per_frame {
+            if self.vblank_wait {
+                break;
+            }
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.remove(&addr);
+    }
+
+    /// Like [`Emu::step`], but stops before executing an instruction at a breakpoint.
+    /// Calling it again after a hit executes that instruction and continues.
+    pub fn step_debug(&mut self) -> Result<StepOutcome, EmuError> {
+        if self.halted || self.quit {
+            return Ok(StepOutcome::Halted);
+        }
+        if self.breakpoints.contains(&self.pc) && self.break_pc != Some(self.pc) {
+            self.break_pc = Some(self.pc);
+            return Ok(StepOutcome::HitBreakpoint(self.pc));
+        }
+        self.break_pc = None;
+        self.step()?;
+        Ok(StepOutcome::Normal)
+    }
+
+    /// Installs a hook called in [`Emu::step`] with the address and value of
+    /// every fetched instruction, before it is executed.
+    pub fn set_trace_hook(&mut self, f: Box<dyn FnMut(u16, Instruction)>) {
+        self.trace_hook = Some(f);
+    }
+
+    pub fn clear_trace_hook(&mut self) {
+        self.trace_hook = None;
+    }
+
+    /// Starts counting executed instructions by category, see [`Emu::opcode_histogram`].
+    pub fn enable_profiling(&mut self) {
+        self.profile.get_or_insert_with(BTreeMap::new);
+    }
+
+    /// How many times each kind of instruction ran since [`Emu::enable_profiling`],
+    /// keyed by [`Instruction::category`]. Empty if profiling is off.
+    pub fn opcode_histogram(&self) -> BTreeMap<&'static str, u64> {
+        self.profile.clone().unwrap_or_default()
+    }
+
+    /// Executes up to `count` instructions, stopping early if the program halts or
+    /// waits for the vertical blank. Returns the number of instructions actually
+    /// executed.
+    pub fn step_n(&mut self, count: u64) -> Result<u64, EmuError> {
+        for i in 0..count {
+            if self.halted || self.vblank_wait {
+                return Ok(i);
+            }
+            self.step()?;
+        }
+        Ok(count)
+    }
+
+    /// Fetches and executes one instruction. Does nothing once halted.
+    pub fn step(&mut self) -> Result<(), EmuError> {
+        if self.halted || self.vblank_wait {
+            return Ok(());
+        }
+        self.apply_playback();
+        let pc = self.pc;
+        let instr = self.fetch()?;
+        if let Some(hook) = self.trace_hook.as_mut() {
+            hook(pc, instr);
+        }
+        #[cfg(feature = "log")]
+        log::trace!("{:04X}: {:04X} {:X?}", pc, instr.0, instr.decode());
+        self.execute(instr)?;
+        if let Some(profile) = self.profile.as_mut() {
+            *profile.entry(instr.category()).or_insert(0) += 1;
+        }
+        self.steps += 1;
+        self.last_cycles = instr.cycles();
+        self.total_cycles += self.last_cycles as u64;
+        Ok(())
+    }
+
+    fn reg(&self, i: u8) -> u8 {
+        assert!(i < 16, "Invalid register index");
+        self.regs[i as usize]
+    }
+
+    // Wraps so that fetching the last word of a 64 KB memory doesn't overflow.
+    fn jump_next(&mut self) {
+        self.pc = self.pc.wrapping_add(2);
+    }
+
+    /// Decrements the delay and sound timers by one if they are non-zero.
+    /// Should be called at 60 Hz, independent of the instruction rate.
+    pub fn tick_timers(&mut self) {
+        self.vblank_wait = false;
+        if self.dt > 0 {
+            self.dt -= 1;
+        }
+        if self.st > 0 {
+            self.st -= 1;
+        }
+        self.update_buzzer();
+    }
+
+    // Start or stop the buzzer as soon as the sound timer becomes non-zero or hits zero.
+    fn update_buzzer(&mut self) {
+        let on = self.st > 0;
+        if on != self.playing {
+            self.playing = on;
+            self.frame.beep_started |= on;
+            self.buzzer.set_playing(on);
+        }
+    }
+
+    fn execute(&mut self, ins: Instruction) -> Result<(), EmuError> {
+        match ins.decode() {
+            (0, 0, 0, 0) => {}
+            (0, 0, 0xE, 0) => {
+                // Clear the selected planes.
+                for plane in self.selected_planes() {
+                    self.replace_plane(plane, [false; HIRES_WIDTH * HIRES_HEIGHT]);
+                }
+                self.display_cleared = true;
+                self.frame.cleared = true;
+            }
+            (0, 0, 0xC, n) => {
+                // Scroll the display down n pixels.
+                self.scroll(0, n as isize);
+            }
+            (0, 0, 0xF, 0xB) => {
+                // Scroll the display right 4 pixels.
+                self.scroll(4, 0);
+            }
+            (0, 0, 0xF, 0xC) => {
+                // Scroll the display left 4 pixels.
+                self.scroll(-4, 0);
+            }
+            (0, 0, 0xF, 0xD) => {
+                // SUPER-CHIP: exit the interpreter.
+                self.halted = true;
+            }
+            (0, 0, 0xF, 0xE) => {
+                // Disable high resolution mode.
+                self.hires = false;
+                self.display = [[false; HIRES_WIDTH * HIRES_HEIGHT]; 2];
+                self.dirty = [true; HIRES_WIDTH * HIRES_HEIGHT];
+            }
+            (0, 0, 0xF, 0xF) => {
+                // Enable high resolution mode.
+                self.hires = true;
+                self.display = [[false; HIRES_WIDTH * HIRES_HEIGHT]; 2];
+                self.dirty = [true; HIRES_WIDTH * HIRES_HEIGHT];
+            }
+            (0, 0, 0xE, 0xE) => {
+                // Return from a subroutine.
+                // The interpreter sets the program counter to the address at the top of the stack, then subtracts 1 from the stack pointer.
+                if self.sp == 0 {
+                    return Err(EmuError::StackUnderflow(self.pc.wrapping_sub(2)));
+                }
+                self.sp -= 1;
+                self.pc = self.stack[self.sp as usize];
+            }
+            (1, _, _, _) => {
+                // A jump to itself is an infinite loop, commonly used to end a program.
+                if ins.nnn() == self.pc.wrapping_sub(2) {
+                    self.halted = true;
+                }
+                self.pc = ins.nnn();
+            }
+            (2, _, _, _) => {
+                if self.sp as usize >= self.stack.len() {
+                    return Err(EmuError::StackOverflow(self.pc.wrapping_sub(2)));
+                }
+                self.stack[self.sp as usize] = self.pc;
+                self.sp += 1;
+                self.pc = ins.nnn();
+            }
+            (3, x, _, _) => {
+                if self.reg(x) == ins.kk() {
+                    self.skip();
+                }
+            }
+            (4, x, _, _) => {
+                if self.reg(x) != ins.kk() {
+                    self.skip();
                 }
+            }
+            (5, x, y, 0) => {
+                if self.reg(x) == self.reg(y) {
+                    self.skip();
+                }
+            }
+            (6, x, _, _) => {
+                self.regs[x as usize] = ins.kk();
+            }
+            (7, x, _, _) => {
+                self.regs[x as usize] = self.reg(x).wrapping_add(ins.kk());
+            }
+            (8, x, y, 0) => {
+                // Vx = Vy.
+                self.regs[x as usize] = self.reg(y);
+            }
+            (8, x, y, 1) => {
+                // Vx |= Vy.
+                self.regs[x as usize] |= self.reg(y);
+            }
+            (8, x, y, 2) => {
+                // Vx &= Vy.
+                self.regs[x as usize] &= self.reg(y);
+            }
+            (8, x, y, 3) => {
+                // Vx ^= Vy.
+                self.regs[x as usize] ^= self.reg(y);
+            }
+            (8, x, y, 4) => {
+                // Vx += Vy.
+                let (sum, overed) = self.reg(x).overflowing_add(self.reg(y));
+                self.regs[x as usize] = sum;
+                // VF is written last so the flag wins when x is 0xF.
+                if overed {
+                    self.regs[0xF] = 1;
+                } else {
+                    self.regs[0xF] = 0;
+                }
+            }
+            (8, x, y, 5) => {
+                // Vx -= Vy.
+                self.sub(x, self.reg(x), self.reg(y));
+            }
+            (8, x, y, 6) => {
+                // Vx = Vy >> 1, or Vx >>= 1.
+                let src = if self.quirks.shift_uses_vy {
+                    self.reg(y)
+                } else {
+                    self.reg(x)
+                };
+                self.regs[x as usize] = src >> 1;
+                self.regs[0xF] = src & 1;
+            }
+            (8, x, y, 7) => {
+                // Vx = Vy - Vx.
+                self.sub(x, self.reg(y), self.reg(x));
+            }
+            (8, x, y, 0xE) => {
+                // Vx = Vy << 1, or Vx <<= 1.
+                let src = if self.quirks.shift_uses_vy {
+                    self.reg(y)
+                } else {
+                    self.reg(x)
+                };
+                self.regs[x as usize] = src << 1;
+                self.regs[0xF] = (src >> 7) & 1;
+            }
+            (9, x, y, 0) => {
+                if self.reg(x) != self.reg(y) {
+                    self.skip();
+                }
+            }
+            (0xA, _, _, _) => {
+                self.r_i = ins.nnn();
+            }
+            (0xB, x, _, _) => {
+                // BNNN jumps to NNN + V0, SUPER-CHIP's BXNN to XNN + Vx.
+                let offset = if self.quirks.jump_uses_vx {
+                    self.reg(x)
+                } else {
+                    self.reg(0)
+                };
+                self.pc = ins.nnn().wrapping_add(offset as u16);
+            }
+            (0xC, x, _, _) => {
+                // Vx = random byte AND kk.
+                let random_byte = self.rng.next_u8();
+                self.regs[x as usize] = random_byte & ins.kk();
+            }
+            (0xD, x, y, n) => {
+                let (width, height) = self.display_dimensions();
+                // In hires mode DXY0 draws a 16x16 sprite stored as two bytes per row.
+                let (sprite_width, rows) = if n == 0 && self.hires {
+                    (16, 16)
+                } else {
+                    (8, n as usize)
+                };
+                let mut start = self.r_i as usize;
+                let mut collision = false;
+                let x = self.reg(x) as usize % width;
+                let y = self.reg(y) as usize % height;
+
+                // With both planes selected, the sprite data for plane 1 follows plane 0's.
+                for plane in self.selected_planes() {
+                    for y_line in 0..rows {
+                        let sprite = if sprite_width == 16 {
+                            let addr = start + y_line * 2;
+                            u16::from_be_bytes([
+                                self.ram.try_read(addr)?,
+                                self.ram.try_read(addr + 1)?,
+                            ])
+                        } else {
+                            (self.ram.try_read(start + y_line)? as u16) << 8
+                        };
+                        let y = y + y_line;
+                        if y >= height && !self.quirks.sprite_wrapping {
+                            break;
+                        }
+                        let y = y % height;
+                        for x_line in 0..sprite_width {
+                            if (sprite & (0x8000 >> x_line)) != 0 {
+                                let mut x = x + x_line;
+                                if self.quirks.sprite_wrapping {
+                                    x %= width;
+                                }
+                                let Some(index) = self.display_index(x, y) else {
+                                    break;
+                                };
+                                if self.display[plane][index] {
+                                    collision = true;
+                                }
+                                self.display[plane][index] ^= true;
+                                self.dirty[index] = true;
+                            }
+                        }
+                    }
+                    start += rows * sprite_width / 8;
+                }
+
+                // VF is always written: 1 if a pixel was turned off on any plane, 0
+                // otherwise, including sprites clipped away entirely. 00E0 leaves it.
+                if collision {
+                    self.regs[0xF] = 1;
+                    self.collisions += 1;
+                } else {
+                    self.regs[0xF] = 0;
+                }
+                self.vblank_wait = self.quirks.display_wait;
+                self.frame.drew = true;
+            }
+            (0xE, x, 9, 0xE) => {
+                // Values above 0xF are no key and never pressed.
+                if self.is_key_pressed(self.reg(x)) {
+                    self.skip();
+                }
+            }
+            (0xE, x, 0xA, 1) => {
+                if !self.is_key_pressed(self.reg(x)) {
+                    self.skip();
+                }
+            }
+            (0xF, 0, 0, 0) => {
+                // XO-CHIP: load a 16-bit address from the next two bytes into I.
+                let pc = self.pc as usize;
+                self.r_i = u16::from_be_bytes([self.ram.try_read(pc)?, self.ram.try_read(pc + 1)?]);
+                self.jump_next();
+            }
+            (0xF, n, 0, 1) => {
+                // XO-CHIP: select the bit planes used by DXYN, 00E0 and scrolling.
+                self.planes = n & 0b11;
+            }
+            #[cfg(feature = "xo_chip")]
+            (0xF, 0, 0, 2) => {
+                // XO-CHIP: load the 16-byte audio pattern from memory at I.
+                for (i, sample) in self.audio.samples.iter_mut().enumerate() {
+                    *sample = self.ram.try_read(self.r_i as usize + i)?;
+                }
+                self.buzzer.set_pattern(&self.audio);
+            }
+            #[cfg(feature = "xo_chip")]
+            (0xF, x, 3, 0xA) => {
+                // XO-CHIP: set the audio playback pitch to Vx.
+                self.audio.pitch = self.reg(x);
+                self.buzzer.set_pattern(&self.audio);
+            }
+            (0xF, x, 0, 7) => {
+                self.regs[x as usize] = self.dt;
+            }
+            (0xF, x, 0, 0xA) => {
+                // Wait for a key press, store the value of the key in Vx.
+                // All execution stops until a key is pressed, then the value of that key is stored in Vx.
+                // Rather than blocking here, the instruction is re-executed on the next step so the
+                // host keeps control and can feed in new key events.
+                let mut pressed = false;
+                for (i, key) in self.keys.iter().enumerate() {
+                    if *key {
+                        self.regs[x as usize] = i as u8;
+                        pressed = true;
+                        break;
+                    }
+                }
+                if !pressed {
+                    self.pc = self.pc.wrapping_sub(2);
+                }
+            }
+            (0xF, x, 1, 5) => {
+                self.dt = self.reg(x);
+            }
+            (0xF, x, 1, 8) => {
+                self.st = self.reg(x);
+                self.update_buzzer();
+            }
+            (0xF, x, 1, 0xE) => {
+                self.r_i = self.r_i.wrapping_add(self.reg(x) as u16);
+                if self.quirks.i_overflow_sets_vf {
+                    self.regs[0xF] = if self.r_i > 0x0FFF { 1 } else { 0 };
+                }
+            }
+            (0xF, x, 2, 9) => {
+                // The value of I is set to the location for the hexadecimal sprite corresponding to the value of Vx
+                self.r_i = self.reg(x) as u16 * 5;
+            }
+            (0xF, x, 3, 0) => {
+                // SUPER-CHIP: point I at the 10-byte big font glyph for the low nibble of Vx.
+                self.r_i = (BIG_FONT_ADDR + (self.reg(x) & 0xF) as usize * 10) as u16;
+            }
+            (0xF, x, 3, 3) => {
+                let vx = self.reg(x);
+                self.ram.try_store(self.r_i as usize, (vx / 100) % 10)?;
+                self.ram.try_store(self.r_i as usize + 1, (vx / 10) % 10)?;
+                self.ram.try_store(self.r_i as usize + 2, vx % 10)?;
+            }
+            (0xF, x, 5, 5) => {
+                assert!(x < 16, "Invalid register index");
+                let start = self.r_i as usize;
+                for i in 0..=x {
+                    self.ram.try_store(start + i as usize, self.reg(i))?;
+                }
+                if self.quirks.load_store_increments_i {
+                    self.r_i = self.r_i.wrapping_add(x as u16 + 1);
+                }
+            }
+            (0xF, x, 6, 5) => {
+                assert!(x < 16, "Invalid register index");
+                let start = self.r_i as usize;
+                for i in 0..=x {
+                    let i = i as usize;
+                    self.regs[i] = self.ram.try_read(start + i)?;
+                }
+                if self.quirks.load_store_increments_i {
+                    self.r_i = self.r_i.wrapping_add(x as u16 + 1);
+                }
+            }
+            (0xF, x, 7, 5) if x < 8 => {
+                // SUPER-CHIP: store V0 to Vx in the RPL user flags.
+                let n = x as usize + 1;
+                self.flags[..n].copy_from_slice(&self.regs[..n]);
+            }
+            (0xF, x, 8, 5) if x < 8 => {
+                // SUPER-CHIP: load V0 to Vx from the RPL user flags.
+                let n = x as usize + 1;
+                self.regs[..n].copy_from_slice(&self.flags[..n]);
+            }
+            // PC is already past the opcode, so there is nothing left to do.
+            _ if self.quirks.skip_unknown_opcodes => {}
+            (5 | 8 | 9, _, _, _) => {
+                return Err(EmuError::InvalidOperandNibble(ins.0));
+            }
+            _ => {
+                return Err(EmuError::UnknownInstruction(ins.0));
+            }
+        }
+        Ok(())
+    }
+
+    // Shifts the display contents by (dx, dy) pixels, clearing the vacated area.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = self.display_dimensions();
+        for plane in self.selected_planes() {
+            let old = &self.display[plane];
+            let mut display = [false; HIRES_WIDTH * HIRES_HEIGHT];
+            for y in 0..height {
+                for x in 0..width {
+                    let src_x = x as isize - dx;
+                    let src_y = y as isize - dy;
+                    if (0..width as isize).contains(&src_x) && (0..height as isize).contains(&src_y)
+                    {
+                        display[y * width + x] = old[src_y as usize * width + src_x as usize];
+                    }
+                }
+            }
+            self.replace_plane(plane, display);
+        }
+    }
+
+    // Indexes of the planes selected by `FN01`.
+    fn selected_planes(&self) -> impl Iterator<Item = usize> {
+        let planes = self.planes;
+        (0..2).filter(move |plane| planes & (1 << plane) != 0)
+    }
+
+    // Swaps in new contents for a plane, marking the pixels that differ as dirty.
+    fn replace_plane(&mut self, plane: usize, display: [bool; HIRES_WIDTH * HIRES_HEIGHT]) {
+        let old = &self.display[plane];
+        for (dirty, (old, new)) in self.dirty.iter_mut().zip(old.iter().zip(display)) {
+            *dirty |= *old != new;
+        }
+        self.display[plane] = display;
+    }
+
+    // Skips the next instruction, including the address after an XO-CHIP `F000`
+    // which makes it 4 bytes long.
+    fn skip(&mut self) {
+        let pc = self.pc as usize;
+        let long = self.ram.0.get(pc..pc + 2) == Some(&[0xF0, 0x00]);
+        self.pc = self.pc.wrapping_add(if long { 4 } else { 2 });
+    }
+
+    // Vx = a - b, VF = NOT borrow. VF is written last so the flag wins when x is 0xF.
+    fn sub(&mut self, x: u8, a: u8, b: u8) {
+        let (result, underflowed) = a.overflowing_sub(b);
+        self.regs[x as usize] = result;
+        if underflowed {
+            self.regs[0xF] = 0;
+        } else {
+            self.regs[0xF] = 1;
+        }
+    }
+
+    // All instructions are 2 bytes long and are stored most-significant-byte first.
+    // The original interpreter has no alignment requirement, so instructions at odd
+    // addresses are read as-is unless the strict alignment quirk is on.
+    // Instructions are read from RAM on every fetch, never cached or predecoded, so
+    // self-modifying code runs what it wrote. `test_self_modifying_code` relies on it.
+    fn fetch(&mut self) -> Result<Instruction, EmuError> {
+        if self.quirks.strict_alignment && !self.pc.is_multiple_of(2) {
+            return Err(EmuError::UnalignedPc(self.pc));
+        }
+        let pc = self.pc as usize;
+        let high_byte = self.ram.try_read(pc)? as u16;
+        let low_byte = self.ram.try_read(pc + 1)? as u16;
+        self.jump_next();
+        Ok(Instruction(high_byte << 8 | low_byte))
+    }
+}
+
+// Every pattern `Instruction::category` knows, in the same order, and whether
+// `Emu::execute` runs it in this build.
+const OPCODES: [(&str, bool); 48] = [
+    ("00E0", true),
+    ("00EE", true),
+    ("00CN", true),
+    ("00FB", true),
+    ("00FC", true),
+    ("00FD", true),
+    ("00FE", true),
+    ("00FF", true),
+    ("0NNN", false),
+    ("1NNN", true),
+    ("2NNN", true),
+    ("3XNN", true),
+    ("4XNN", true),
+    ("5XY0", true),
+    ("6XNN", true),
+    ("7XNN", true),
+    ("8XY0", true),
+    ("8XY1", true),
+    ("8XY2", true),
+    ("8XY3", true),
+    ("8XY4", true),
+    ("8XY5", true),
+    ("8XY6", true),
+    ("8XY7", true),
+    ("8XYE", true),
+    ("9XY0", true),
+    ("ANNN", true),
+    ("BNNN", true),
+    ("CXNN", true),
+    ("DXYN", true),
+    ("EX9E", true),
+    ("EXA1", true),
+    ("F000", true),
+    ("FN01", true),
+    ("F002", cfg!(feature = "xo_chip")),
+    ("FX07", true),
+    ("FX0A", true),
+    ("FX15", true),
+    ("FX18", true),
+    ("FX1E", true),
+    ("FX29", true),
+    ("FX30", true),
+    ("FX33", true),
+    ("FX3A", cfg!(feature = "xo_chip")),
+    ("FX55", true),
+    ("FX65", true),
+    ("FX75", true),
+    ("FX85", true),
+];
+
+/// Each opcode pattern of CHIP-8 and its extensions, e.g. `"8XY6"`, and whether
+/// this build executes it. `0NNN` machine code routines are never run, and the
+/// XO-CHIP audio opcodes `F002` and `FX3A` need the `xo_chip` feature.
+pub fn opcode_coverage() -> Vec<(&'static str, bool)> {
+    OPCODES.to_vec()
+}
+
+#[derive(Clone, Copy)]
+pub struct Instruction(u16);
+
+impl From<u16> for Instruction {
+    fn from(opcode: u16) -> Self {
+        Self(opcode)
+    }
+}
+
+impl PartialEq<u16> for Instruction {
+    fn eq(&self, other: &u16) -> bool {
+        self.0 == *other
+    }
+}
+
+impl Debug for Instruction {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{:>04x}", self.0)
+    }
+}
+
+impl Instruction {
+    pub fn decode(&self) -> (u8, u8, u8, u8) {
+        //  0  1  2  3  4  5  6  7  8  9  0  1  2  3  4  5
+        // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+        // |    a      |     b     |     c     |     d     |
+        // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+        let a = ((self.0 & 0xF000) >> 12) as u8;
+        let b = ((self.0 & 0x0F00) >> 8) as u8;
+        let c = ((self.0 & 0x00F0) >> 4) as u8;
+        let d = (self.0 & 0x000F) as u8;
+        (a, b, c, d)
+    }
+
+    /// The raw 16-bit opcode.
+    pub fn opcode(&self) -> u16 {
+        self.0
+    }
+
+    pub fn nnn(&self) -> u16 {
+        self.0 & 0x0FFF
+    }
+
+    pub fn kk(&self) -> u8 {
+        (self.0 & 0x00FF) as u8
+    }
+
+    /// The opcode pattern this instruction matches, e.g. `"8XY4"` or `"DXYN"`,
+    /// or `"unknown"` if it isn't a supported instruction.
+    pub fn category(&self) -> &'static str {
+        match self.decode() {
+            (0, 0, 0xE, 0) => "00E0",
+            (0, 0, 0xE, 0xE) => "00EE",
+            (0, 0, 0xC, _) => "00CN",
+            (0, 0, 0xF, 0xB) => "00FB",
+            (0, 0, 0xF, 0xC) => "00FC",
+            (0, 0, 0xF, 0xD) => "00FD",
+            (0, 0, 0xF, 0xE) => "00FE",
+            (0, 0, 0xF, 0xF) => "00FF",
+            (0, _, _, _) => "0NNN",
+            (1, _, _, _) => "1NNN",
+            (2, _, _, _) => "2NNN",
+            (3, _, _, _) => "3XNN",
+            (4, _, _, _) => "4XNN",
+            (5, _, _, 0) => "5XY0",
+            (6, _, _, _) => "6XNN",
+            (7, _, _, _) => "7XNN",
+            (8, _, _, 0) => "8XY0",
+            (8, _, _, 1) => "8XY1",
+            (8, _, _, 2) => "8XY2",
+            (8, _, _, 3) => "8XY3",
+            (8, _, _, 4) => "8XY4",
+            (8, _, _, 5) => "8XY5",
+            (8, _, _, 6) => "8XY6",
+            (8, _, _, 7) => "8XY7",
+            (8, _, _, 0xE) => "8XYE",
+            (9, _, _, 0) => "9XY0",
+            (0xA, _, _, _) => "ANNN",
+            (0xB, _, _, _) => "BNNN",
+            (0xC, _, _, _) => "CXNN",
+            (0xD, _, _, _) => "DXYN",
+            (0xE, _, 9, 0xE) => "EX9E",
+            (0xE, _, 0xA, 1) => "EXA1",
+            (0xF, 0, 0, 0) => "F000",
+            (0xF, _, 0, 1) => "FN01",
+            (0xF, 0, 0, 2) => "F002",
+            (0xF, _, 0, 7) => "FX07",
+            (0xF, _, 0, 0xA) => "FX0A",
+            (0xF, _, 1, 5) => "FX15",
+            (0xF, _, 1, 8) => "FX18",
+            (0xF, _, 1, 0xE) => "FX1E",
+            (0xF, _, 2, 9) => "FX29",
+            (0xF, _, 3, 0) => "FX30",
+            (0xF, _, 3, 3) => "FX33",
+            (0xF, _, 3, 0xA) => "FX3A",
+            (0xF, _, 5, 5) => "FX55",
+            (0xF, _, 6, 5) => "FX65",
+            (0xF, _, 7, 5) => "FX75",
+            (0xF, _, 8, 5) => "FX85",
+            _ => "unknown",
+        }
+    }
+
+    /// Approximate cost in COSMAC VIP machine cycles (8 clocks, about 4.5 µs each),
+    /// after the interpreter timings measured on real hardware. Drawing and memory
+    /// transfers scale with the number of rows and registers. Extension opcodes the
+    /// VIP never had, and skips taken, are charged like a simple instruction.
+    pub fn cycles(&self) -> u32 {
+        let (_, x, _, n) = self.decode();
+        match self.category() {
+            "00E0" => 24,
+            "00EE" | "1NNN" | "2NNN" | "BNNN" => 23,
+            "3XNN" | "4XNN" | "ANNN" => 12,
+            "5XY0" | "9XY0" | "EX9E" | "EXA1" => 16,
+            "6XNN" => 6,
+            "8XY0" | "8XY1" | "8XY2" | "8XY3" | "8XY4" | "8XY5" | "8XY6" | "8XY7" | "8XYE" => 44,
+            "CXNN" => 36,
+            "DXYN" => 26 + 66 * n as u32,
+            "FX1E" => 19,
+            "FX29" => 20,
+            "FX33" => 204,
+            "FX55" | "FX65" => 18 + 14 * (x as u32 + 1),
+            _ => 10,
+        }
+    }
+}
+
+/// Forks the machine, e.g. to explore different inputs from the same point. The
+/// copy is silent and has no trace hook, since those usually drive a single
+/// frontend. Its random source continues the original's sequence if the source
+/// supports [`RandomSource::clone_box`].
+impl Clone for Emu {
+    fn clone(&self) -> Self {
+        Self {
+            pc: self.pc,
+            sp: self.sp,
+            r_i: self.r_i,
+            regs: self.regs,
+            stack: self.stack,
+            ram: self.ram.clone(),
+            keys: self.keys,
+            display: self.display,
+            planes: self.planes,
+            dirty: self.dirty,
+            display_cleared: self.display_cleared,
+            brightness: self.brightness,
+            fade_decay: self.fade_decay,
+            hires: self.hires,
+            dt: self.dt,
+            st: self.st,
+            buzzer: Box::new(NullBuzzer),
+            playing: self.playing,
+            #[cfg(feature = "xo_chip")]
+            audio: self.audio,
+            flags: self.flags,
+            cycles_per_frame: self.cycles_per_frame,
+            steps: self.steps,
+            last_cycles: self.last_cycles,
+            total_cycles: self.total_cycles,
+            collisions: self.collisions,
+            quirks: self.quirks,
+            breakpoints: self.breakpoints.clone(),
+            break_pc: self.break_pc,
+            trace_hook: None,
+            profile: self.profile.clone(),
+            rng: self.rng.clone_box().unwrap_or_else(default_rng),
+            font: self.font.clone(),
+            start_addr: self.start_addr,
+            rom_hash: self.rom_hash,
+            rom_len: self.rom_len,
+            recording: self.recording.clone(),
+            playback: self.playback.clone(),
+            rewind: self.rewind.clone(),
+            rewind_depth: self.rewind_depth,
+            paused: self.paused,
+            halted: self.halted,
+            vblank_wait: self.vblank_wait,
+            frame: self.frame,
+            quit: self.quit,
+            _priv: (),
+        }
+    }
+}
+
+/// Configures an [`Emu`] with several settings at once, which the `with_*`
+/// constructors can't combine, e.g. a start address beyond 4 KB in a larger
+/// memory. Settings left out keep their defaults.
+#[derive(Default)]
+pub struct EmuBuilder {
+    quirks: Quirks,
+    cycles_per_frame: Option<u32>,
+    ram_size: Option<usize>,
+    start_addr: Option<u16>,
+    font: Option<Vec<u8>>,
+    rng: Option<Box<dyn RandomSource>>,
+}
+
+impl EmuBuilder {
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// See [`Emu::set_cycles_per_frame`].
+    pub fn cycles_per_frame(mut self, cycles: u32) -> Self {
+        self.cycles_per_frame = Some(cycles);
+        self
+    }
+
+    /// See [`Emu::with_ram_size`].
+    pub fn ram_size(mut self, size: usize) -> Self {
+        self.ram_size = Some(size);
+        self
+    }
+
+    /// See [`Emu::with_start_addr`], the address may lie beyond 4 KB with a larger
+    /// [`EmuBuilder::ram_size`].
+    pub fn start_addr(mut self, addr: u16) -> Self {
+        self.start_addr = Some(addr);
+        self
+    }
+
+    /// See [`Emu::set_font`].
+    pub fn font(mut self, font: &[u8]) -> Self {
+        self.font = Some(font.to_vec());
+        self
+    }
+
+    /// See [`Emu::with_rng`].
+    pub fn rng(mut self, rng: impl RandomSource + 'static) -> Self {
+        self.rng = Some(Box::new(rng));
+        self
+    }
+
+    /// Creates the emulator, failing like the constructor or setter of the first
+    /// invalid setting.
+    pub fn build(self) -> Result<Emu, EmuError> {
+        let mut emu = match self.ram_size {
+            Some(size) => Emu::with_ram_size(size)?,
+            None => Emu::new(),
+        };
+        if let Some(addr) = self.start_addr {
+            emu.set_start_addr(addr)?;
+        }
+        if let Some(font) = self.font {
+            emu.set_font(&font)?;
+        }
+        if let Some(cycles) = self.cycles_per_frame {
+            emu.set_cycles_per_frame(cycles);
+        }
+        if let Some(rng) = self.rng {
+            emu.rng = rng;
+        }
+        emu.quirks = self.quirks;
+        Ok(emu)
+    }
+}
+
+impl Default for Emu {
+    fn default() -> Self {
+        Self {
+            pc: START_ADDR,
+            sp: 0,
+            r_i: 0,
+            regs: [0; 16],
+            stack: [0; 16],
+            ram: Ram::default(),
+            keys: [false; 16],
+            display: [[false; HIRES_WIDTH * HIRES_HEIGHT]; 2],
+            planes: 1,
+            dirty: [false; HIRES_WIDTH * HIRES_HEIGHT],
+            display_cleared: false,
+            brightness: [0; HIRES_WIDTH * HIRES_HEIGHT],
+            fade_decay: 0,
+            hires: false,
+            dt: 0,
+            st: 0,
+            buzzer: Box::new(NullBuzzer),
+            #[cfg(feature = "xo_chip")]
+            audio: AudioPattern::default(),
+            flags: [0; 8],
+            playing: false,
+            cycles_per_frame: DEFAULT_CYCLES_PER_FRAME,
+            steps: 0,
+            last_cycles: 0,
+            total_cycles: 0,
+            collisions: 0,
+            quirks: Quirks::default(),
+            breakpoints: BTreeSet::new(),
+            break_pc: None,
+            trace_hook: None,
+            profile: None,
+            rng: default_rng(),
+            font: FONT_SET.to_vec(),
+            start_addr: START_ADDR,
+            rom_hash: fnv1a(&[]),
+            rom_len: 0,
+            recording: None,
+            playback: VecDeque::new(),
+            rewind: VecDeque::new(),
+            rewind_depth: 0,
+            paused: false,
+            halted: false,
+            vblank_wait: false,
+            frame: FrameReport::default(),
+            quit: false,
+            _priv: (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{rc::Rc, string::ToString};
+    use core::cell::RefCell;
+
+    #[test]
+    fn test_pong2() -> anyhow::Result<()> {
+        let mut emu = Emu::new();
+        emu.load(include_bytes!("../../roms/PONG2"))?;
+        assert_eq!(emu.step_n(1000000)?, 1000000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tick_timers() -> anyhow::Result<()> {
+        let mut emu = Emu::new();
+        // V0 = 60, DT = V0
+        emu.load(&[0x60, 0x3C, 0xF0, 0x15])?;
+        emu.step()?;
+        emu.step()?;
+        assert_eq!(emu.dt, 60);
+        for _ in 0..59 {
+            emu.tick_timers();
+        }
+        assert_eq!(emu.dt, 1);
+        emu.tick_timers();
+        assert_eq!(emu.dt, 0);
+        emu.tick_timers();
+        assert_eq!(emu.dt, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_timers() {
+        let mut emu = Emu::new();
+        emu.set_delay_timer(5);
+        assert_eq!(emu.get_delay_timer(), 5);
+        emu.tick_timers();
+        assert_eq!(emu.get_delay_timer(), 4);
+
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        emu.set_buzzer(Box::new(RecordingBuzzer(calls.clone())));
+        emu.set_sound_timer(2);
+        assert_eq!(emu.get_sound_timer(), 2);
+        assert_eq!(*calls.borrow(), vec![true]);
+        emu.set_sound_timer(0);
+        assert_eq!(*calls.borrow(), vec![true, false]);
+    }
+
+    fn run_rom(rom: &[u8], steps: usize) -> anyhow::Result<Emu> {
+        let mut emu = Emu::new();
+        emu.load(rom)?;
+        for _ in 0..steps {
+            emu.step()?;
+        }
+        Ok(emu)
+    }
+
+    #[test]
+    fn test_shift_flags() -> anyhow::Result<()> {
+        // V0 = 0x80, V0 <<= 1
+        let emu = run_rom(&[0x60, 0x80, 0x80, 0x0E], 2)?;
+        assert_eq!(emu.regs[0], 0x00);
+        assert_eq!(emu.regs[0xF], 1);
+
+        // V0 = 0x01, V0 >>= 1
+        let emu = run_rom(&[0x60, 0x01, 0x80, 0x06], 2)?;
+        assert_eq!(emu.regs[0], 0x00);
+        assert_eq!(emu.regs[0xF], 1);
+
+        // VF = 1, V0 = 0x40, V0 <<= 1
+        let emu = run_rom(&[0x6F, 0x01, 0x60, 0x40, 0x80, 0x0E], 3)?;
+        assert_eq!(emu.regs[0], 0x80);
+        assert_eq!(emu.regs[0xF], 0);
+
+        // VF = 1, V0 = 0x02, V0 >>= 1
+        let emu = run_rom(&[0x6F, 0x01, 0x60, 0x02, 0x80, 0x06], 3)?;
+        assert_eq!(emu.regs[0], 0x01);
+        assert_eq!(emu.regs[0xF], 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift_quirk() -> anyhow::Result<()> {
+        // V0 = 0x0F, V1 = 0x81, V0 >>= V1 / V0 <<= V1
+        for (op, vip, schip) in [(0x06, (0x40, 1), (0x07, 1)), (0x0E, (0x02, 1), (0x1E, 0))] {
+            let rom = [0x60, 0x0F, 0x61, 0x81, 0x80, 0x10 | op];
+            for (shift_uses_vy, (v0, vf)) in [(true, vip), (false, schip)] {
+                let mut emu = Emu::with_quirks(Quirks {
+                    shift_uses_vy,
+                    ..Quirks::default()
+                });
+                emu.load(&rom)?;
+                emu.step_n(3)?;
+                assert_eq!((emu.regs[0], emu.regs[0xF]), (v0, vf));
+                assert_eq!(emu.regs[1], 0x81);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_coverage() -> anyhow::Result<()> {
+        let coverage = opcode_coverage();
+        let implemented = coverage.iter().filter(|(_, done)| *done).count();
+        assert_eq!(implemented, if cfg!(feature = "xo_chip") { 47 } else { 45 });
+
+        // Run a sample of each pattern to check the table against `execute`.
+        for (pattern, done) in coverage {
+            let opcode = u16::from_str_radix(&pattern.replace(['X', 'Y', 'N'], "1"), 16)?;
+            assert_eq!(Instruction::from(opcode).category(), pattern);
+            let mut emu = Emu::from_rom(&opcode.to_be_bytes())?;
+            let unknown = matches!(emu.step(), Err(EmuError::UnknownInstruction(_)));
+            assert_eq!(!unknown, done, "{}", pattern);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_pixel() -> anyhow::Result<()> {
+        let rom = [
+            0x60, 0x00, // V0 = 0
+            0x61, 0x3F, // V1 = 63
+            0x62, 0x1F, // V2 = 31
+            0xA2, 0x1A, // I = 0x21A
+            0xD0, 0x01, // Draw at (0, 0)
+            0xD1, 0x01, // Draw at (63, 0)
+            0xD0, 0x21, // Draw at (0, 31)
+            0xD1, 0x21, // Draw at (63, 31)
+            0x00, 0xFF, // Hires
+            0x61, 0x7F, // V1 = 127
+            0x62, 0x3F, // V2 = 63
+            0xD1, 0x21, // Draw at (127, 63)
+            0x12, 0x18, // Loop forever
+            0x80, // Sprite
+        ];
+        let mut emu = run_rom(&rom, 8)?;
+        for (x, y) in [(0, 0), (63, 0), (0, 31), (63, 31)] {
+            assert!(emu.pixel(x, y), "({}, {})", x, y);
+        }
+        assert!(!emu.pixel(1, 0));
+        assert!(!emu.pixel(62, 31));
+        assert!(!emu.pixel(64, 0));
+        assert!(!emu.pixel(0, 32));
+        assert_eq!(emu.get_display().iter().filter(|&&on| on).count(), 4);
+
+        emu.step_n(4)?;
+        assert!(emu.pixel(127, 63));
+        assert!(!emu.pixel(128, 63));
+        assert!(!emu.pixel(127, 64));
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_immediate_wraps() -> anyhow::Result<()> {
+        // VF = 0x42, V0 = 0xFF, V0 += 0x05
+        let emu = run_rom(&[0x6F, 0x42, 0x60, 0xFF, 0x70, 0x05], 3)?;
+        assert_eq!(emu.regs[0], 0x04);
+        assert_eq!(emu.regs[0xF], 0x42);
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_frame() -> anyhow::Result<()> {
+        let mut emu = Emu::new();
+        emu.load(include_bytes!("../../roms/PONG2"))?;
+        emu.set_cycles_per_frame(7);
+        emu.run_frame()?;
+        assert_eq!(emu.get_steps(), 7);
+        emu.run_frame()?;
+        assert_eq!(emu.get_steps(), 14);
+
+        emu.set_clock_hz(600);
+        assert_eq!(emu.cycles_per_frame(), 10);
+        emu.set_clock_hz(0);
+        assert_eq!(emu.cycles_per_frame(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_self_modifying_code() -> anyhow::Result<()> {
+        let rom = [
+            0x60, 0x62, // V0 = 0x62
+            0x61, 0x2A, // V1 = 0x2A
+            0xA2, 0x08, // I = 0x208
+            0xF1, 0x55, // Store V0..V1 at I, overwriting the next instruction
+            0x00, 0xE0, // Becomes V2 = 0x2A
+            0x00, 0xE0, // Replaced with write_mem
+        ];
+        let mut emu = run_rom(&rom, 5)?;
+        assert_eq!(emu.regs[2], 0x2A);
+        assert_eq!(emu.get_pc(), 0x20A);
+
+        // V3 = 0x17
+        assert!(emu.write_mem(0x20A, 0x63));
+        assert!(emu.write_mem(0x20B, 0x17));
+        emu.step()?;
+        assert_eq!(emu.regs[3], 0x17);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_store_quirk() -> anyhow::Result<()> {
+        // I = 0x300, V0..V2 = [RAM 0x300..0x303]
+        let rom = [0xA3, 0x00, 0xF2, 0x65];
+
+        let mut emu = Emu::with_quirks(Quirks {
+            load_store_increments_i: true,
+            ..Quirks::default()
+        });
+        emu.load(&rom)?;
+        emu.step()?;
+        emu.step()?;
+        assert_eq!(emu.r_i, 0x303);
+
+        let mut emu = Emu::with_quirks(Quirks {
+            load_store_increments_i: false,
+            ..Quirks::default()
+        });
+        emu.load(&rom)?;
+        emu.step()?;
+        emu.step()?;
+        assert_eq!(emu.r_i, 0x300);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_store_single_register() -> anyhow::Result<()> {
+        // X = 0 stores or loads V0 only. V0 = 0x42, V1 = 0x43, I = 0x300, store,
+        // clear V0 and V1, I = 0x300, load
+        let rom = [
+            0x60, 0x42, 0x61, 0x43, 0xA3, 0x00, 0xF0, 0x55, 0x60, 0x00, 0x61, 0x00, 0xA3, 0x00,
+            0xF0, 0x65,
+        ];
+        for (increments, i) in [(true, 0x301), (false, 0x300)] {
+            let mut emu = Emu::with_quirks(Quirks {
+                load_store_increments_i: increments,
+                ..Quirks::default()
+            });
+            emu.load(&rom)?;
+            emu.step_n(4)?;
+            assert_eq!(emu.r_i, i);
+            assert_eq!(emu.mem_slice(0x300..0x302), [0x42, 0x00]);
+            emu.step_n(4)?;
+            assert_eq!(emu.r_i, i);
+            assert_eq!(emu.regs[..2], [0x42, 0x00]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_load_state() -> anyhow::Result<()> {
+        let mut emu = Emu::new();
+        emu.load(include_bytes!("../../roms/PONG2"))?;
+        for _ in 0..5000 {
+            emu.step()?;
+        }
+        let state = emu.save_state();
+        let display = emu.get_display().to_vec();
+        for _ in 0..5000 {
+            emu.step()?;
+        }
+        emu.load_state(state.clone())?;
+        assert_eq!(emu.get_display(), display);
+        assert_eq!(emu.save_state(), state);
+
+        // Corrupt snapshots are rejected and leave the machine alone
+        let pc = emu.pc;
+        let bad_sp = EmuState {
+            sp: 17,
+            ..state.clone()
+        };
+        assert_eq!(emu.load_state(bad_sp), Err(EmuError::InvalidState));
+        let bad_pc = EmuState {
+            pc: 0x1000,
+            ..state.clone()
+        };
+        assert_eq!(emu.load_state(bad_pc), Err(EmuError::InvalidState));
+        assert_eq!(emu.pc, pc);
+        let full_stack = EmuState { sp: 16, ..state };
+        emu.load_state(full_stack)?;
+        assert_eq!(emu.call_stack().len(), 16);
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_state_serde_round_trip() -> anyhow::Result<()> {
+        let mut emu = Emu::new();
+        emu.load(include_bytes!("../../roms/PONG2"))?;
+        for _ in 0..5000 {
+            emu.step()?;
+        }
+        let state = emu.save_state();
+        let json = serde_json::to_string(&state)?;
+        let restored: EmuState = serde_json::from_str(&json)?;
+        assert_eq!(restored, state);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset() -> anyhow::Result<()> {
+        let rom = include_bytes!("../../roms/PONG2");
+        let mut emu = Emu::new();
+        emu.load(rom)?;
+        for _ in 0..1000 {
+            emu.step()?;
+        }
+        emu.reset();
+        assert_eq!(emu.pc, 0x200);
+        assert_eq!(emu.get_steps(), 0);
+        assert_eq!(emu.read_mem(0x200), Some(rom[0]));
+
+        emu.reset_and_reload(&[0x12, 0x00])?;
+        assert_eq!(emu.read_mem(0x200), Some(0x12));
+        assert_eq!(emu.read_mem(0x202), Some(0x00));
+
+        // A ROM that doesn't fit leaves the current one running.
+        emu.step()?;
+        assert!(emu.reset_and_reload(&[0; 4096]).is_err());
+        assert_eq!(emu.read_mem(0x200), Some(0x12));
+        assert_eq!(emu.get_steps(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_register_accessors() {
+        let mut emu = Emu::new();
+        emu.set_reg(5, 0xAB);
+        assert_eq!(emu.get_reg(5), 0xAB);
+        emu.set_i(0x345);
+        assert_eq!(emu.get_i(), 0x345);
+    }
+
+    #[test]
+    fn test_dump_state() {
+        let mut emu = Emu::new();
+        assert!(emu.dump_state().contains("PC=0200"));
+
+        emu.set_reg(0xA, 0x3C);
+        emu.set_i(0x123);
+        assert_eq!(
+            emu.dump_state(),
+            "PC=0200 I=0123 SP=0 DT=00 ST=00\n\
+             V0=00 V1=00 V2=00 V3=00 V4=00 V5=00 V6=00 V7=00\n\
+             V8=00 V9=00 VA=3C VB=00 VC=00 VD=00 VE=00 VF=00"
+        );
+    }
+
+    #[test]
+    fn test_set_pc() -> anyhow::Result<()> {
+        // V0 = 1, V0 = 2
+        let mut emu = Emu::new();
+        emu.load(&[0x60, 0x01, 0x60, 0x02])?;
+        assert_eq!(emu.get_pc(), 0x200);
+        assert_eq!(emu.get_sp(), 0);
+        emu.set_pc(0x202)?;
+        emu.step()?;
+        assert_eq!(emu.regs[0], 2);
+        assert_eq!(emu.get_pc(), 0x204);
+
+        assert_eq!(emu.set_pc(0x1000), Err(EmuError::InvalidPc(0x1000)));
+        assert_eq!(emu.set_pc(0xFFF), Err(EmuError::InvalidPc(0xFFF)));
+        assert_eq!(emu.get_pc(), 0x204);
+        emu.set_pc(0x203)?;
+        assert_eq!(emu.get_pc(), 0x203);
+
+        let mut emu = Emu::with_quirks(Quirks {
+            strict_alignment: true,
+            ..Quirks::default()
+        });
+        assert_eq!(emu.set_pc(0x203), Err(EmuError::InvalidPc(0x203)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_odd_pc() -> anyhow::Result<()> {
+        // jump 0x203, padding, V0 = 0x42 at 0x203
+        let rom = [0x12, 0x03, 0x00, 0x60, 0x42];
+        let emu = run_rom(&rom, 2)?;
+        assert_eq!(emu.regs[0], 0x42);
+        assert_eq!(emu.pc, 0x205);
+
+        let mut emu = Emu::with_quirks(Quirks {
+            strict_alignment: true,
+            ..Quirks::default()
+        });
+        emu.load(&rom)?;
+        emu.step()?;
+        assert_eq!(emu.step(), Err(EmuError::UnalignedPc(0x203)));
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid register index")]
+    fn test_set_reg_out_of_range() {
+        Emu::new().set_reg(16, 0);
+    }
+
+    #[test]
+    fn test_memory_accessors() {
+        let mut emu = Emu::new();
+        assert!(emu.write_mem(4095, 0x42));
+        assert_eq!(emu.read_mem(4095), Some(0x42));
+        assert!(!emu.write_mem(4096, 0x42));
+        assert_eq!(emu.read_mem(4096), None);
+
+        assert_eq!(emu.mem_slice(4094..4096), &[0x00, 0x42]);
+        assert_eq!(emu.mem_slice(4095..5000), &[0x42]);
+        assert!(emu.mem_slice(4096..5000).is_empty());
+        assert_eq!(emu.mem_slice(0..5).len(), 5);
+    }
+
+    #[test]
+    fn test_load_oversized_rom() {
+        let mut emu = Emu::new();
+        let err = emu.load(&[0; 4000]).unwrap_err();
+        assert_eq!(
+            err,
+            EmuError::RomTooLarge {
+                size: 4000,
+                max: 3584
+            }
+        );
+        assert_eq!(err.to_string(), "ROM too large: 4000 bytes, max 3584");
+        assert!(emu.load(&[0; 3584]).is_ok());
+    }
+
+    #[test]
+    fn test_ram_size() -> anyhow::Result<()> {
+        let mut emu = Emu::with_ram_size(0x10000)?;
+        assert_eq!(emu.ram.len(), 0x10000);
+        emu.load_at(0xFF00, &[0x12, 0x34])?;
+        // I = 0xFF00, skip the long load if V0 == 0, load V0-V1 from I, jump back
+        // to the skip for another round, which now doesn't skip.
+        emu.load(&[
+            0xF0, 0x00, 0xFF, 0x00, 0x30, 0x00, 0xF0, 0x00, 0x00, 0x00, 0xF1, 0x65, 0x12, 0x04,
+        ])?;
+        emu.step_n(2)?;
+        assert_eq!(emu.r_i, 0xFF00);
+        assert_eq!(emu.pc, 0x20A);
+        emu.step_n(3)?;
+        assert_eq!(emu.regs[..2], [0x12, 0x34]);
+        assert_eq!(emu.pc, 0x206);
+        emu.step()?;
+        assert_eq!(emu.r_i, 0x0000);
+        assert_eq!(emu.pc, 0x20A);
+
+        // The last word of memory can be executed
+        emu.load_at(0xFFFE, &[0x60, 0x42])?;
+        emu.set_pc(0xFFFE)?;
+        emu.step()?;
+        assert_eq!(emu.regs[0], 0x42);
+        assert_eq!(emu.pc, 0);
+
+        // Instructions there that go back to their own address point at it
+        emu.load_at(0xFFFE, &[0xF0, 0x0A])?;
+        emu.set_pc(0xFFFE)?;
+        emu.step()?;
+        assert_eq!(emu.pc, 0xFFFE);
+        emu.load_at(0xFFFE, &[0x00, 0xEE])?;
+        assert_eq!(emu.step(), Err(EmuError::StackUnderflow(0xFFFE)));
+        // Fill the stack by calling 0x300 from itself
+        emu.load_at(0x300, &[0x23, 0x00])?;
+        emu.set_pc(0x300)?;
+        emu.step_n(16)?;
+        emu.load_at(0xFFFE, &[0x23, 0x00])?;
+        emu.set_pc(0xFFFE)?;
+        assert_eq!(emu.step(), Err(EmuError::StackOverflow(0xFFFE)));
+
+        // Reloading keeps the size
+        emu.reset_and_reload(&[])?;
+        assert_eq!(emu.ram.len(), 0x10000);
+
+        assert_eq!(
+            Emu::with_ram_size(0x800).err(),
+            Some(EmuError::InvalidRamSize(0x800))
+        );
+        assert!(Emu::with_ram_size(0x10001).is_err());
+        assert!(Emu::new().load_at(0xFF00, &[1]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_rom() -> anyhow::Result<()> {
+        let emu = Emu::from_rom(&[0x60, 0x42])?;
+        assert_eq!(emu.read_mem(0x200), Some(0x60));
+        assert_eq!(emu.rom_len(), 2);
+        assert_eq!(
+            Emu::from_rom(&[0; 4000]).err(),
+            Some(EmuError::RomTooLarge {
+                size: 4000,
+                max: 3584
+            })
+        );
+
+        #[cfg(feature = "std")]
+        {
+            let emu = Emu::from_rom_file("../roms/PONG2")?;
+            assert_eq!(
+                emu.rom_hash(),
+                Emu::from_rom(include_bytes!("../../roms/PONG2"))?.rom_hash()
+            );
+            assert!(Emu::from_rom_file("../roms/missing").is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_at() -> anyhow::Result<()> {
+        let mut emu = Emu::new();
+        emu.load(&[0x12, 0x34])?;
+        emu.load_at(0x400, &[0xAB, 0xCD])?;
+        assert_eq!(emu.mem_slice(0x200..0x202), [0x12, 0x34]);
+        assert_eq!(emu.mem_slice(0x400..0x402), [0xAB, 0xCD]);
+        assert_eq!(emu.mem_slice(0..5), &FONT_SET[..5]);
+
+        assert!(emu.load_at(0xFFE, &[1, 2]).is_ok());
+        assert_eq!(
+            emu.load_at(0xFFF, &[1, 2]),
+            Err(EmuError::RomTooLarge { size: 2, max: 1 })
+        );
+        assert!(emu.load_at(0x1000, &[1]).is_err());
+        assert!(emu.load_at(0x1000, &[]).is_ok());
+        assert_eq!(
+            emu.load_at(0x1001, &[]),
+            Err(EmuError::OutOfBoundsWrite(0x1001))
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_get_diaplay_alias() -> anyhow::Result<()> {
+        let emu = run_rom(include_bytes!("../../roms/PONG2"), 1000)?;
+        assert_eq!(emu.get_diaplay(), emu.get_display());
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_press_release() {
+        let mut emu = Emu::new();
+        emu.key_press(0xA);
+        assert!(emu.keys[0xA]);
+        emu.key_release(0xA);
+        assert!(!emu.keys[0xA]);
+
+        emu.key_press(0x10);
+        emu.key_release(0xFF);
+        assert_eq!(emu.keys, [false; 16]);
+    }
+
+    #[test]
+    fn test_pressed_keys() {
+        let mut emu = Emu::new();
+        assert_eq!(emu.pressed_keys().count(), 0);
+        emu.key_press(9);
+        emu.key_press(3);
+        assert_eq!(emu.pressed_keys().collect::<Vec<_>>(), vec![3, 9]);
+        assert!(emu.is_key_pressed(3));
+        assert!(!emu.is_key_pressed(4));
+        assert!(!emu.is_key_pressed(0x10));
+    }
+
+    #[test]
+    fn test_skip_if_key() -> anyhow::Result<()> {
+        // V0 = 5, skip if key V0 pressed, V1 = 1, skip if key V0 not pressed, V2 = 1
+        let rom = [0x60, 0x05, 0xE0, 0x9E, 0x61, 0x01, 0xE0, 0xA1, 0x62, 0x01];
+        let emu = run_rom(&rom, 5)?;
+        assert_eq!(emu.regs[1..3], [1, 0]);
+
+        let mut emu = run_rom(&rom, 1)?;
+        emu.with_keys_held(&[5, 0xA], |emu| emu.step_n(3))?;
+        assert_eq!(emu.pressed_keys().count(), 0);
+        assert_eq!(emu.regs[1..3], [0, 1]);
+        assert_eq!(emu.pc, 0x20A);
+
+        // V0 = 0x10 is not a key and counts as not pressed
+        let rom = [0x60, 0x10, 0xE0, 0x9E, 0x61, 0x01, 0xE0, 0xA1, 0x62, 0x01];
+        let mut emu = run_rom(&rom, 1)?;
+        emu.with_keys_held(&[0, 0xF], |emu| emu.step_n(3))?;
+        assert_eq!(emu.regs[1..3], [1, 0]);
+        assert_eq!(emu.pc, 0x20A);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wait_for_key() -> anyhow::Result<()> {
+        // V3 = 0x42, wait for key into V3
+        let mut emu = run_rom(&[0x63, 0x42, 0xF3, 0x0A], 1)?;
+        for _ in 0..3 {
+            emu.step()?;
+            assert_eq!(emu.pc, 0x202);
+            assert_eq!(emu.regs[3], 0x42);
+        }
+        emu.key_press(7);
+        emu.step()?;
+        assert_eq!(emu.pc, 0x204);
+        assert_eq!(emu.regs[3], 7);
+        Ok(())
+    }
+
+    #[test]
+    fn test_breakpoints() -> anyhow::Result<()> {
+        let mut emu = Emu::new();
+        emu.load(&[0x60, 0x01, 0x61, 0x02, 0x62, 0x03, 0x63, 0x04])?;
+        emu.add_breakpoint(0x204);
+        assert_eq!(emu.step_debug()?, StepOutcome::Normal);
+        assert_eq!(emu.step_debug()?, StepOutcome::Normal);
+        assert_eq!(emu.step_debug()?, StepOutcome::HitBreakpoint(0x204));
+        assert_eq!(emu.regs[2], 0);
+        assert_eq!(emu.step_debug()?, StepOutcome::Normal);
+        assert_eq!(emu.regs[2], 3);
+
+        emu.reset();
+        emu.remove_breakpoint(0x204);
+        for _ in 0..4 {
+            assert_eq!(emu.step_debug()?, StepOutcome::Normal);
+        }
+
+        emu.quit();
+        assert_eq!(emu.step_debug()?, StepOutcome::Halted);
+        Ok(())
+    }
+
+    #[test]
+    fn test_trace_hook() -> anyhow::Result<()> {
+        let trace = Rc::new(RefCell::new(Vec::new()));
+        let mut emu = Emu::new();
+        emu.load(include_bytes!("../../roms/PONG2"))?;
+        let sink = trace.clone();
+        emu.set_trace_hook(Box::new(move |pc, ins| {
+            sink.borrow_mut().push((pc, ins.opcode()));
+        }));
+        for _ in 0..5 {
+            emu.step()?;
+        }
+        assert_eq!(
+            *trace.borrow(),
+            vec![
+                (0x200, 0x22F6),
+                (0x2F6, 0x6B20),
+                (0x2F8, 0x6C00),
+                (0x2FA, 0xA2EA),
+                (0x2FC, 0xDBC1),
+            ]
+        );
+
+        emu.clear_trace_hook();
+        emu.step()?;
+        assert_eq!(trace.borrow().len(), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hires() -> anyhow::Result<()> {
+        // HIGH, I = 0x20C, V0 = 120, V1 = 0, DRW V0, V1, 0, LOW
+        let rom = [
+            0x00, 0xFF, 0xA2, 0x0C, 0x60, 0x78, 0x61, 0x00, 0xD0, 0x10, 0x00, 0xFE,
+        ];
+        let mut emu = run_rom(&rom, 1)?;
+        assert_eq!(emu.display_dimensions(), (128, 64));
+        assert_eq!(emu.get_display().len(), 128 * 64);
+
+        // The sprite data is the 16x16 block of RAM following the program.
+        for addr in 0x20C..0x22C {
+            emu.write_mem(addr, 0xFF);
+        }
+        for _ in 0..4 {
+            emu.step()?;
+        }
+        let lit = emu.get_display().iter().filter(|p| **p).count();
+        // Clipped at the right edge: 8 of the 16 columns fit.
+        assert_eq!(lit, 8 * 16);
+        assert!(emu.get_display()[15 * 128 + 127]);
+
+        emu.step()?;
+        assert_eq!(emu.display_dimensions(), (64, 32));
+        assert!(emu.get_display().iter().all(|p| !p));
+        Ok(())
+    }
+
+    #[test]
+    fn test_scroll() -> anyhow::Result<()> {
+        // V0 = 10, I = 0x20C, DRW V0, V0, 1, SCD 3, SCR, SCL
+        let rom = [
+            0x60, 0x0A, 0xA2, 0x0C, 0xD0, 0x01, 0x00, 0xC3, 0x00, 0xFB, 0x00, 0xFC, 0x80,
+        ];
+        let mut emu = run_rom(&rom, 3)?;
+        assert!(emu.get_display()[10 * 64 + 10]);
+
+        emu.step()?;
+        let lit: Vec<usize> = (0..64 * 32).filter(|i| emu.get_display()[*i]).collect();
+        assert_eq!(lit, vec![13 * 64 + 10]);
+
+        emu.step()?;
+        let lit: Vec<usize> = (0..64 * 32).filter(|i| emu.get_display()[*i]).collect();
+        assert_eq!(lit, vec![13 * 64 + 14]);
+
+        emu.step()?;
+        let lit: Vec<usize> = (0..64 * 32).filter(|i| emu.get_display()[*i]).collect();
+        assert_eq!(lit, vec![13 * 64 + 10]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scroll_clears_vacated_rows() -> anyhow::Result<()> {
+        // V0 = 0, I = 0x20A, DRW V0, V0, 1, SCD 3, SCL
+        let rom = [
+            0x60, 0x00, 0xA2, 0x0A, 0xD0, 0x01, 0x00, 0xC3, 0x00, 0xFC, 0xFF,
+        ];
+        let emu = run_rom(&rom, 5)?;
+        let display = emu.get_display();
+        assert!(display[..3 * 64].iter().all(|p| !p));
+        // Row 3 had pixels 0..8 lit, after scrolling left 4 only 0..4 remain.
+        assert_eq!(display[3 * 64..4 * 64].iter().filter(|p| **p).count(), 4);
+        assert!(display[3 * 64 + 3]);
+        assert!(!display[3 * 64 + 4]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_seed_rng() -> anyhow::Result<()> {
+        let mut emu = Emu::new();
+        emu.seed_rng(42);
+        // V0 = random & 0x0F
+        emu.load(&[0xC0, 0x0F])?;
+        emu.step()?;
+        let expected = StdRng::seed_from_u64(42).gen::<u8>() & 0x0F;
+        assert_eq!(emu.regs[0], expected);
+        Ok(())
+    }
+
+    struct Counter(u8);
+
+    impl RandomSource for Counter {
+        fn next_u8(&mut self) -> u8 {
+            self.0 = self.0.wrapping_add(0x11);
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_with_rng() -> anyhow::Result<()> {
+        let mut emu = Emu::with_rng(Counter(0));
+        // V0 = random & 0xFF, V1 = random & 0x0F
+        emu.load(&[0xC0, 0xFF, 0xC1, 0x0F])?;
+        emu.step()?;
+        emu.step()?;
+        assert_eq!(emu.regs[0], 0x11);
+        assert_eq!(emu.regs[1], 0x02);
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_fixed_random() -> anyhow::Result<()> {
+        // V0 = random & 0x0F
+        let mut emu = Emu::with_fixed_random(vec![0xAB]);
+        emu.load(&[0xC0, 0x0F])?;
+        emu.step()?;
+        assert_eq!(emu.regs[0], 0x0B);
+
+        // V0..V3 = random
+        let mut emu = Emu::with_fixed_random(vec![1, 2]);
+        emu.load(&[0xC0, 0xFF, 0xC1, 0xFF, 0xC2, 0xFF, 0xC3, 0xFF])?;
+        emu.step_n(3)?;
+        assert_eq!(emu.regs[..3], [1, 2, 1]);
+        // A clone continues the same sequence
+        let mut clone = emu.clone();
+        emu.step()?;
+        clone.step()?;
+        assert_eq!((emu.regs[3], clone.regs[3]), (2, 2));
+
+        let mut emu = Emu::with_fixed_random(vec![]);
+        emu.load(&[0xC0, 0xFF])?;
+        emu.regs[0] = 0x42;
+        emu.step()?;
+        assert_eq!(emu.regs[0], 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_big_font() -> anyhow::Result<()> {
+        // I = big "0", V0 = 0xA, I = big "A"
+        let mut emu = run_rom(&[0xF0, 0x30, 0x60, 0x0A, 0xF0, 0x30], 1)?;
+        assert_eq!(emu.r_i, BIG_FONT_ADDR as u16);
+        assert_eq!(emu.read_mem(emu.r_i), Some(0xFF));
+        emu.step_n(2)?;
+        assert_eq!(emu.r_i, BIG_FONT_ADDR as u16 + 100);
+        assert_eq!(emu.read_mem(emu.r_i), Some(0x7E));
+        // The small font is still in place below it
+        assert_eq!(emu.read_mem(0), Some(0xF0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_font() -> anyhow::Result<()> {
+        let font: Vec<u8> = (0..80).collect();
+        let mut emu = Emu::new();
+        emu.set_font(&font)?;
+        assert_eq!(emu.read_mem(7), Some(7));
+        emu.load(&[0x00, 0xE0])?;
+        assert_eq!(emu.read_mem(79), Some(79));
+
+        assert_eq!(
+            emu.set_font(&[0; 0x201]),
+            Err(EmuError::FontTooLarge {
+                size: 0x201,
+                max: 0x200
+            })
+        );
+        assert_eq!(emu.read_mem(79), Some(79));
+        Ok(())
+    }
+
+    #[test]
+    fn test_builder() -> anyhow::Result<()> {
+        let quirks = Quirks {
+            shift_uses_vy: false,
+            ..Quirks::default()
+        };
+        let font: Vec<u8> = (0..80).collect();
+        let mut emu = Emu::builder()
+            .quirks(quirks)
+            .cycles_per_frame(20)
+            .ram_size(0x2000)
+            .start_addr(0x1000)
+            .font(&font)
+            .rng(Counter(0))
+            .build()?;
+        assert_eq!(emu.quirks(), quirks);
+        assert_eq!(emu.cycles_per_frame(), 20);
+        assert_eq!(emu.ram.len(), 0x2000);
+        assert_eq!(emu.get_pc(), 0x1000);
+        // V0 = random
+        emu.load(&[0xC0, 0xFF])?;
+        assert_eq!(emu.read_mem(0x1000), Some(0xC0));
+        assert_eq!(emu.read_mem(7), Some(7));
+        emu.step()?;
+        assert_eq!(emu.regs[0], 0x11);
+
+        // The start address has to fit the default 4 KB of memory
+        assert_eq!(
+            Emu::builder().start_addr(0x1000).build().err(),
+            Some(EmuError::InvalidStartAddress(0x1000))
+        );
+        assert_eq!(
+            Emu::builder().ram_size(0x100).build().err(),
+            Some(EmuError::InvalidRamSize(0x100))
+        );
+        assert!(Emu::builder().font(&[0; 0x300]).build().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_addr() -> anyhow::Result<()> {
+        let mut emu = Emu::with_start_addr(0x600)?;
+        assert_eq!(emu.pc, 0x600);
+        emu.load(&[0x6A, 0x42])?;
+        assert_eq!(emu.read_mem(0x600), Some(0x6A));
+        assert_eq!(emu.read_mem(0x200), Some(0x00));
+        emu.step()?;
+        assert_eq!(emu.regs[0xA], 0x42);
+
+        emu.reset();
+        assert_eq!(emu.pc, 0x600);
+        assert!(emu.load(&[0; 2561]).is_err());
+
+        assert!(matches!(
+            Emu::with_start_addr(0x1000),
+            Err(EmuError::InvalidStartAddress(0x1000))
+        ));
+        assert!(Emu::with_start_addr(0x10).is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_load_file_and_reader() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(format!("shinobu8-test-{}.ch8", std::process::id()));
+        std::fs::write(&path, [0x60, 0x2A, 0x12, 0x02])?;
+        let mut emu = Emu::new();
+        let result = emu.load_file(&path);
+        std::fs::remove_file(&path)?;
+        result?;
+        assert_eq!(emu.mem_slice(0x200..0x204), &[0x60, 0x2A, 0x12, 0x02]);
+
+        let mut emu = Emu::new();
+        emu.load_reader(&[0x61, 0x07][..])?;
+        assert_eq!(emu.mem_slice(0x200..0x202), &[0x61, 0x07]);
+
+        assert!(Emu::new().load_file(&path).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_ppm() -> anyhow::Result<()> {
+        // V0 = 0, I = glyph "0", DRW V0, V0, 5
+        let emu = run_rom(&[0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05], 3)?;
+
+        let header = b"P6\n64 32\n255\n";
+        let ppm = emu.to_ppm();
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(ppm.len(), header.len() + 64 * 32 * 3);
+
+        let header = b"P6\n128 64\n255\n";
+        let ppm = emu.to_ppm_scaled(2);
+        assert_eq!(&ppm[..header.len()], header);
+        let pixel = |x: usize, y: usize| {
+            let i = header.len() + (y * 128 + x) * 3;
+            &ppm[i..i + 3]
+        };
+        // The top row of "0" is 0xF0: pixels 0..4 lit, scaled to 0..8.
+        assert_eq!(pixel(0, 0), &[0xFF; 3]);
+        assert_eq!(pixel(7, 1), &[0xFF; 3]);
+        assert_eq!(pixel(8, 0), &[0x00; 3]);
+        // Second row is 0x90: pixel 1 is unlit.
+        assert_eq!(pixel(2, 2), &[0x00; 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_and_replay_input() -> anyhow::Result<()> {
+        // Wait for a key, draw its glyph at (V1, V2), move V1 right and repeat.
+        let rom = [0xF0, 0x0A, 0xF0, 0x29, 0xD1, 0x25, 0x71, 0x05, 0x12, 0x00];
+        let mut emu = Emu::new();
+        emu.load(&rom)?;
+        emu.start_recording();
+        for (key, hold) in [(0x3, 4), (0xA, 9), (0x7, 2)] {
+            for _ in 0..7 {
+                emu.step()?;
+            }
+            emu.key_press(key);
+            for _ in 0..hold {
+                emu.step()?;
+            }
+            emu.key_release(key);
+        }
+        for _ in 0..10 {
+            emu.step()?;
+        }
+        let events = emu.take_recording();
+        assert_eq!(events.len(), 6);
+        assert_eq!(
+            events[0],
+            InputEvent {
+                step: 7,
+                key: 0x3,
+                pressed: true
+            }
+        );
+        let steps = emu.get_steps();
+        let display = emu.get_display().to_vec();
+
+        emu.reset();
+        emu.play_recording(events);
+        for _ in 0..steps {
+            emu.step()?;
+        }
+        assert_eq!(emu.get_display(), display);
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_with() -> anyhow::Result<()> {
+        let mut emu = Emu::new();
+        emu.load(include_bytes!("../../roms/PONG2"))?;
+        let mut frames = 0;
+        emu.run_with(|display| {
+            assert_eq!(display.len(), 64 * 32);
+            frames += 1;
+            frames < 10
+        })?;
+        assert_eq!(frames, 10);
+        assert_eq!(emu.get_steps(), 10 * emu.cycles_per_frame() as u64);
+
+        emu.quit();
+        emu.run_with(|_| panic!("no frame should run after quit"))?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_run_paced() -> anyhow::Result<()> {
+        let mut emu = Emu::new();
+        emu.load(include_bytes!("../../roms/PONG2"))?;
+        let start = std::time::Instant::now();
+        let mut frames = 0;
+        emu.run_paced(|_| {
+            frames += 1;
+            frames < FRAME_RATE
+        })?;
+        // 60 frames with 59 sleeps in between, generous bounds for loaded machines.
+        let elapsed = start.elapsed().as_secs_f64();
+        assert!((0.9..3.0).contains(&elapsed), "took {}s", elapsed);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pause() -> anyhow::Result<()> {
+        let mut emu = Emu::new();
+        emu.load(&[0x60, 0x01, 0xF0, 0x15, 0x12, 0x00])?;
+        emu.run_frame()?;
+        let steps = emu.get_steps();
+        let dt = emu.dt;
+
+        emu.set_paused(true);
+        assert!(emu.is_paused());
+        emu.run_frame()?;
+        assert_eq!(emu.get_steps(), steps);
+        assert_eq!(emu.dt, dt);
+
+        emu.set_paused(false);
+        emu.run_frame()?;
+        assert_eq!(emu.get_steps(), steps * 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_flag_written_last() -> anyhow::Result<()> {
+        // VF = 0xFF, V1 = 0x02, VF += V1
+        let emu = run_rom(&[0x6F, 0xFF, 0x61, 0x02, 0x8F, 0x14], 3)?;
+        assert_eq!(emu.regs[0xF], 1);
+        // VF = 0x10, V1 = 0x01, VF += V1
+        let emu = run_rom(&[0x6F, 0x10, 0x61, 0x01, 0x8F, 0x14], 3)?;
+        assert_eq!(emu.regs[0xF], 0);
+
+        // VF = 0x01, V1 = 0x02, VF -= V1
+        let emu = run_rom(&[0x6F, 0x01, 0x61, 0x02, 0x8F, 0x15], 3)?;
+        assert_eq!(emu.regs[0xF], 0);
+        // VF = 0x01, V1 = 0x02, VF = V1 - VF
+        let emu = run_rom(&[0x6F, 0x01, 0x61, 0x02, 0x8F, 0x17], 3)?;
+        assert_eq!(emu.regs[0xF], 1);
+
+        // VF = 0x02, VF >>= 1
+        let emu = run_rom(&[0x6F, 0x02, 0x8F, 0xF6], 2)?;
+        assert_eq!(emu.regs[0xF], 0);
+        // VF = 0x81, VF <<= 1
+        let emu = run_rom(&[0x6F, 0x81, 0x8F, 0xFE], 2)?;
+        assert_eq!(emu.regs[0xF], 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stack_overflow() -> anyhow::Result<()> {
+        // Each level calls the next instruction.
+        let rom: Vec<u8> = (0..17u16)
+            .flat_map(|i| (0x2000 | (0x202 + i * 2)).to_be_bytes())
+            .collect();
+        let mut emu = run_rom(&rom, 16)?;
+        assert_eq!(emu.sp, 16);
+        let err = emu.step().unwrap_err();
+        assert_eq!(err, EmuError::StackOverflow(0x220));
+        assert_eq!(err.to_string(), "Stack overflow at 0x220");
+        Ok(())
+    }
+
+    #[test]
+    fn test_call_stack() -> anyhow::Result<()> {
+        // Call 0x204, which calls 0x208
+        let rom = [0x22, 0x04, 0x00, 0x00, 0x22, 0x08, 0x00, 0x00, 0x00, 0xEE];
+        let mut emu = run_rom(&rom, 1)?;
+        assert_eq!(emu.call_stack(), [0x202]);
+        emu.step()?;
+        assert_eq!(emu.call_stack(), [0x202, 0x206]);
+        emu.step()?;
+        assert_eq!(emu.call_stack(), [0x202]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stack_underflow() {
+        let mut emu = Emu::new();
+        emu.load(&[0x00, 0xEE]).unwrap();
+        let err = emu.step().unwrap_err();
+        assert_eq!(err, EmuError::StackUnderflow(0x200));
+        assert_eq!(err.to_string(), "Stack underflow at 0x200");
+    }
+
+    #[test]
+    fn test_halt_on_self_jump() -> anyhow::Result<()> {
+        let mut emu = run_rom(&[0x60, 0x01, 0x12, 0x02], 1)?;
+        assert!(!emu.is_halted());
+        emu.step()?;
+        assert!(emu.is_halted());
+        assert_eq!(emu.pc, 0x202);
+        assert_eq!(emu.get_steps(), 2);
+
+        emu.step()?;
+        emu.run_frame()?;
+        assert_eq!(emu.get_steps(), 2);
+        assert_eq!(emu.step_debug()?, StepOutcome::Halted);
+        emu.run_with(|_| panic!("no frame should run once halted"))?;
+
+        emu.reset();
+        assert!(!emu.is_halted());
+        Ok(())
+    }
+
+    #[test]
+    fn test_exit() -> anyhow::Result<()> {
+        let mut emu = run_rom(&[0x00, 0xFD, 0x60, 0x01], 1)?;
+        assert!(emu.is_halted());
+        assert_eq!(emu.pc, 0x202);
+        assert_eq!(emu.step_n(10)?, 0);
+        assert_eq!(emu.regs[0], 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_n_stops_when_halted() -> anyhow::Result<()> {
+        let mut emu = Emu::new();
+        emu.load(&[0x60, 0x01, 0x61, 0x02, 0x12, 0x04])?;
+        assert_eq!(emu.step_n(10)?, 3);
+        assert!(emu.is_halted());
+        assert_eq!(emu.step_n(10)?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_i_overflow_quirk() -> anyhow::Result<()> {
+        // I = 0xFFE, V0 = 0x03, I += V0
+        let rom = [0xAF, 0xFE, 0x60, 0x03, 0xF0, 0x1E];
+
+        let emu = run_rom(&rom, 3)?;
+        assert_eq!(emu.r_i, 0x1001);
+        assert_eq!(emu.regs[0xF], 0);
+
+        let mut emu = Emu::with_quirks(Quirks {
+            i_overflow_sets_vf: true,
+            ..Quirks::default()
+        });
+        emu.load(&rom)?;
+        emu.step_n(3)?;
+        assert_eq!(emu.r_i, 0x1001);
+        assert_eq!(emu.regs[0xF], 1);
+
+        // I = 0xFFE, V0 = 0x01, I += V0
+        let mut emu = Emu::with_quirks(emu.quirks());
+        emu.load(&[0xAF, 0xFE, 0x6F, 0x01, 0x60, 0x01, 0xF0, 0x1E])?;
+        emu.step_n(4)?;
+        assert_eq!(emu.r_i, 0xFFF);
+        assert_eq!(emu.regs[0xF], 0);
+
+        // I = 0xFFFF, V0 = 0x02, I += V0 wraps around
+        let mut emu = Emu::new();
+        emu.set_i(0xFFFF);
+        emu.load(&[0x60, 0x02, 0xF0, 0x1E])?;
+        emu.step_n(2)?;
+        assert_eq!(emu.r_i, 0x0001);
+        Ok(())
+    }
 
-                if collision {
-                    self.regs[0xF] = 1;
-                } else {
-                    self.regs[0xF] = 0;
-                }
-            }
-            (0xE, x, 9, 0xE) => {
-                if self.keys[self.reg(x) as usize] {
-                    self.jump_next();
-                }
-            }
-            (0xE, x, 0xA, 1) => {
-                if !self.keys[self.reg(x) as usize] {
-                    self.jump_next();
-                }
-            }
-            (0xF, x, 0, 7) => {
-                self.regs[x as usize] = self.dt;
-            }
-            (0xF, x, 0, 0xA) => {
-                // Wait for a key press, store the value of the key in Vx.
-                // All execution stops until a key is pressed, then the value of that key is stored in Vx.
-                let mut pressed = false;
-                for (i, key) in self.keys.iter().enumerate() {
-                    if *key {
-                        self.regs[x as usize] = i as u8;
-                        pressed = true;
-                        break;
-                    }
-                }
-                if !pressed {
-                    self.pc -= 2;
-                }
-            }
-            (0xF, x, 1, 5) => {
-                self.dt = self.reg(x);
-            }
-            (0xF, x, 1, 8) => {
-                self.st = self.reg(x);
-            }
-            (0xF, x, 1, 0xE) => {
-                self.r_i = self.r_i.wrapping_add(self.reg(x) as u16);
-            }
-            (0xF, x, 2, 9) => {
-                // The value of I is set to the location for the hexadecimal sprite corresponding to the value of Vx
-                self.r_i = self.reg(x) as u16 * 5;
-            }
-            (0xF, x, 3, 3) => {
-                let vx = self.reg(x);
-                self.ram.store(self.r_i as usize, (vx / 100) % 10);
-                self.ram.store(self.r_i as usize + 1, (vx / 10) % 10);
-                self.ram.store(self.r_i as usize + 2, vx % 10);
-            }
-            (0xF, x, 5, 5) => {
-                assert!(x < 16, "Invalid register index");
-                let start = self.r_i as usize;
-                for i in 0..=x {
-                    self.ram.store(start + i as usize, self.reg(i));
-                }
-            }
-            (0xF, x, 6, 5) => {
-                assert!(x < 16, "Invalid register index");
-                let start = self.r_i as usize;
-                for i in 0..=x {
-                    let i = i as usize;
-                    self.regs[i] = self.ram.read(start + i);
-                }
-            }
-            _ => {
-                return Err(anyhow::anyhow!("Unknown instruction: {:?}", ins));
-            }
+    #[test]
+    fn test_out_of_bounds_i() -> anyhow::Result<()> {
+        // I = 0xFFE, store V0..V2 / load V0..V2 / BCD / draw 3 rows
+        let cases = [
+            ([0xF2, 0x55], EmuError::OutOfBoundsWrite(0x1000)),
+            ([0xF2, 0x65], EmuError::OutOfBoundsRead(0x1000)),
+            ([0xF0, 0x33], EmuError::OutOfBoundsWrite(0x1000)),
+            ([0xD0, 0x03], EmuError::OutOfBoundsRead(0x1000)),
+        ];
+        for (opcode, expected) in cases {
+            let mut emu = run_rom(&[0xAF, 0xFE, opcode[0], opcode[1]], 1)?;
+            assert_eq!(emu.step(), Err(expected));
         }
         Ok(())
     }
 
-    fn sub(&mut self, x: u8, y: u8) -> u8 {
-        let (result, underflowed) = x.overflowing_sub(y);
-        if underflowed {
-            self.regs[0xF] = 0;
-        } else {
-            self.regs[0xF] = 1;
+    #[test]
+    fn test_invalid_operand_nibble() -> anyhow::Result<()> {
+        for opcode in [0x5125u16, 0x9121, 0x812F] {
+            let mut emu = run_rom(&opcode.to_be_bytes(), 0)?;
+            assert_eq!(emu.step(), Err(EmuError::InvalidOperandNibble(opcode)));
         }
-        result
+        let mut emu = run_rom(&[0x51, 0x25], 0)?;
+        assert_eq!(
+            emu.step().unwrap_err().to_string(),
+            "Invalid last nibble in instruction: 0x5125"
+        );
+        Ok(())
     }
 
-    // All instructions are 2 bytes long and are stored most-significant-byte first.
-    fn fetch(&mut self) -> Instruction {
-        assert!(self.pc % 2 == 0, "PC is not aligned");
-        let pc = self.pc as usize;
-        let high_byte = self.ram.read(pc) as u16;
-        let low_byte = self.ram.read(pc + 1) as u16;
-        self.jump_next();
-        Instruction(high_byte << 8 | low_byte)
+    #[test]
+    fn test_clipped_draw_collision() -> anyhow::Result<()> {
+        // VF = 1, V0 = 63, I = sprite, draw it with only its first pixel on screen,
+        // clear, sprite 0x7F
+        let rom = [
+            0x6F, 0x01, 0x60, 0x3F, 0xA2, 0x0C, 0xD0, 0x01, 0x00, 0xE0, 0x00, 0x00, 0x7F,
+        ];
+        let mut emu = run_rom(&rom, 4)?;
+        assert_eq!(emu.regs[0xF], 0);
+        assert!(!emu.get_display().contains(&true));
+
+        emu.regs[0xF] = 1;
+        emu.step()?;
+        assert_eq!(emu.regs[0xF], 1);
+
+        // A collision on either plane sets VF
+        let rom = [
+            0xF2, 0x01, 0xA2, 0x0A, 0xD0, 0x01, 0xF3, 0x01, 0xD0, 0x01, 0x80, 0x80,
+        ];
+        let mut emu = run_rom(&rom, 3)?;
+        assert_eq!(emu.regs[0xF], 0);
+        emu.step_n(2)?;
+        assert_eq!(emu.regs[0xF], 1);
+        assert!(!emu.get_plane(1).contains(&true));
+        assert!(emu.get_plane(0)[0]);
+        Ok(())
     }
-}
 
-pub struct Instruction(u16);
+    #[test]
+    fn test_collision_count() -> anyhow::Result<()> {
+        // I = sprite, draw it at (0, 0), (4, 0), (20, 0), then (0, 0) again, sprite 0xFF
+        let rom = [
+            0xA2, 0x12, 0x61, 0x04, 0x62, 0x14, 0xD0, 0x01, 0xD1, 0x01, 0xD2, 0x01, 0xD0, 0x01,
+            0x12, 0x0E, 0x00, 0x00, 0xFF,
+        ];
+        let mut emu = run_rom(&rom, 4)?;
+        assert_eq!(emu.collision_count(), 0);
+        emu.step()?;
+        assert_eq!(emu.regs[0xF], 1);
+        assert_eq!(emu.collision_count(), 1);
+        emu.step()?;
+        assert_eq!(emu.regs[0xF], 0);
+        assert_eq!(emu.collision_count(), 1);
+        emu.step()?;
+        assert_eq!(emu.collision_count(), 2);
 
-impl PartialEq<u16> for Instruction {
-    fn eq(&self, other: &u16) -> bool {
-        self.0 == *other
+        emu.reset_collision_count();
+        assert_eq!(emu.collision_count(), 0);
+        emu.reset();
+        emu.step_n(5)?;
+        assert_eq!(emu.collision_count(), 1);
+        emu.reset();
+        assert_eq!(emu.collision_count(), 0);
+        Ok(())
     }
-}
 
-impl Debug for Instruction {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{:>04x}", self.0)
+    #[test]
+    fn test_jump_with_offset() -> anyhow::Result<()> {
+        // V0 = 0x10, V3 = 0x20, jump to 0x300 plus an offset
+        let rom = [0x60, 0x10, 0x63, 0x20, 0xB3, 0x00];
+        let emu = run_rom(&rom, 3)?;
+        assert_eq!(emu.pc, 0x310);
+
+        let mut emu = Emu::with_quirks(Quirks {
+            jump_uses_vx: true,
+            ..Quirks::default()
+        });
+        emu.load(&rom)?;
+        emu.step_n(3)?;
+        assert_eq!(emu.pc, 0x320);
+
+        // The target can be past the end of memory, fetching from there fails.
+        let mut emu = run_rom(&[0x60, 0xFF, 0xBF, 0xFF], 2)?;
+        assert_eq!(emu.pc, 0x10FE);
+        assert_eq!(emu.step(), Err(EmuError::OutOfBoundsRead(0x10FE)));
+        Ok(())
     }
-}
 
-impl Instruction {
-    pub fn decode(&self) -> (u8, u8, u8, u8) {
-        //  0  1  2  3  4  5  6  7  8  9  0  1  2  3  4  5
-        // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
-        // |    a      |     b     |     c     |     d     |
-        // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
-        let a = ((self.0 & 0xF000) >> 12) as u8;
-        let b = ((self.0 & 0x0F00) >> 8) as u8;
-        let c = ((self.0 & 0x00F0) >> 4) as u8;
-        let d = (self.0 & 0x000F) as u8;
-        (a, b, c, d)
+    #[test]
+    fn test_skip_unknown_opcodes() -> anyhow::Result<()> {
+        // V0 = 1, unknown, V1 = 2, unknown, halt
+        let rom = [0x60, 0x01, 0xE1, 0x23, 0x61, 0x02, 0x5A, 0xB1, 0x12, 0x08];
+        let mut emu = Emu::with_quirks(Quirks {
+            skip_unknown_opcodes: true,
+            ..Quirks::default()
+        });
+        emu.load(&rom)?;
+        let traced = Rc::new(RefCell::new(Vec::new()));
+        let log = traced.clone();
+        emu.set_trace_hook(Box::new(move |pc, ins| log.borrow_mut().push((pc, ins.0))));
+        emu.run_with(|_| true)?;
+        assert!(emu.is_halted());
+        assert_eq!(emu.regs[..2], [1, 2]);
+        assert!(traced.borrow().contains(&(0x202, 0xE123)));
+        assert!(traced.borrow().contains(&(0x206, 0x5AB1)));
+        Ok(())
     }
 
-    pub fn nnn(&self) -> u16 {
-        self.0 & 0x0FFF
+    #[test]
+    fn test_unknown_instruction() -> anyhow::Result<()> {
+        let mut emu = run_rom(&[0x60, 0x01, 0xE1, 0x23], 1)?;
+        let err = emu.step().unwrap_err();
+        assert_eq!(err, EmuError::UnknownInstruction(0xE123));
+        assert_eq!(err.to_string(), "Unknown instruction: 0xe123");
+
+        // Converts into anyhow::Error with `?`.
+        let err = run_rom(&[0xE1, 0x23], 1).err().unwrap();
+        assert_eq!(
+            err.downcast_ref::<EmuError>(),
+            Some(&EmuError::UnknownInstruction(0xE123))
+        );
+        Ok(())
     }
 
-    pub fn kk(&self) -> u8 {
-        (self.0 & 0x00FF) as u8
+    #[test]
+    fn test_dirty_pixels() -> anyhow::Result<()> {
+        // Draw the "0" glyph at (2, 1), then clear the screen
+        let rom = [0x60, 0x02, 0x61, 0x01, 0xA0, 0x00, 0xD0, 0x15, 0x00, 0xE0];
+        let mut emu = run_rom(&rom, 3)?;
+        assert_eq!(emu.dirty_pixels().count(), 0);
+
+        emu.step()?;
+        let lit: Vec<_> = emu
+            .get_display()
+            .iter()
+            .enumerate()
+            .filter(|(_, &on)| on)
+            .map(|(i, _)| (i % SCREEN_WIDTH, i / SCREEN_WIDTH))
+            .collect();
+        // 0xF0 0x90 0x90 0x90 0xF0
+        assert_eq!(lit.len(), 14);
+        assert!(lit
+            .iter()
+            .all(|&(x, y)| (2..6).contains(&x) && (1..6).contains(&y)));
+        assert_eq!(emu.dirty_pixels().collect::<Vec<_>>(), lit);
+
+        emu.clear_dirty();
+        assert_eq!(emu.dirty_pixels().count(), 0);
+        assert!(!emu.display_cleared());
+        emu.step()?;
+        assert_eq!(emu.dirty_pixels().collect::<Vec<_>>(), lit);
+        assert!(emu.display_cleared());
+        emu.clear_dirty();
+        assert!(!emu.display_cleared());
+        Ok(())
     }
-}
 
-impl Default for Emu {
-    fn default() -> Self {
-        Self {
-            pc: START_ADDR,
-            sp: 0,
-            r_i: 0,
-            regs: [0; 16],
-            stack: [0; 16],
-            ram: Ram::new(),
-            keys: [false; 16],
-            display: [false; 64 * 32],
-            dt: 0,
-            st: 0,
-            quit: Mutex::new(false),
-            _priv: (),
+    #[test]
+    fn test_display_diff() {
+        let mut emu = Emu::new();
+        let mut previous = vec![false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        previous[SCREEN_WIDTH + 3] = true;
+        previous[5] = true;
+        emu.display[0][5] = true;
+        emu.display[0][2 * SCREEN_WIDTH + 63] = true;
+
+        assert_eq!(
+            emu.display_diff(&previous),
+            vec![(3, 1, false), (63, 2, true)]
+        );
+        assert!(emu.display_diff(emu.get_display()).is_empty());
+
+        emu.hires = true;
+        assert_eq!(
+            emu.display_diff(&previous).len(),
+            HIRES_WIDTH * HIRES_HEIGHT
+        );
+    }
+
+    #[test]
+    fn test_xo_chip_planes() -> anyhow::Result<()> {
+        // Select plane 2, draw the "0" glyph at (0, 0)
+        let emu = run_rom(&[0xF2, 0x01, 0xA0, 0x00, 0xD0, 0x05], 3)?;
+        assert!(emu.get_plane(0).iter().all(|&on| !on));
+        assert_eq!(&emu.get_plane(1)[..5], &[true, true, true, true, false]);
+        assert_eq!(&emu.get_color_display()[..5], &[2, 2, 2, 2, 0]);
+        assert_eq!(emu.regs[0xF], 0);
+
+        // Select both planes and draw "0" to plane 1 and "1" (the next 5 bytes) to plane 2,
+        // then clear plane 1 only
+        let rom = [0xF3, 0x01, 0xA0, 0x00, 0xD0, 0x05, 0xF1, 0x01, 0x00, 0xE0];
+        let mut emu = run_rom(&rom, 3)?;
+        // 0xF0 / 0x20
+        assert_eq!(&emu.get_color_display()[..5], &[1, 1, 3, 1, 0]);
+
+        emu.step_n(2)?;
+        assert!(emu.get_plane(0).iter().all(|&on| !on));
+        assert_eq!(&emu.get_plane(1)[..5], &[false, false, true, false, false]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rpl_flags() -> anyhow::Result<()> {
+        // V0..V3 = 1..4, store V0..V3 in flags, V0..V3 = 0, load flags into V0..V1
+        let rom = [
+            0x60, 0x01, 0x61, 0x02, 0x62, 0x03, 0x63, 0x04, 0xF3, 0x75, 0x60, 0x00, 0x61, 0x00,
+            0x62, 0x00, 0x63, 0x00, 0xF1, 0x85,
+        ];
+        let mut emu = run_rom(&rom, 10)?;
+        assert_eq!(emu.get_flags(), [1, 2, 3, 4, 0, 0, 0, 0]);
+        assert_eq!(&emu.regs[..4], &[1, 2, 0, 0]);
+
+        // Flags survive a reset, loading all of them back
+        emu.reset();
+        emu.load(&[0xF3, 0x85])?;
+        emu.step()?;
+        assert_eq!(&emu.regs[..4], &[1, 2, 3, 4]);
+
+        // Only 8 flags exist
+        emu.reset();
+        emu.load(&[0xF8, 0x75])?;
+        assert_eq!(emu.step(), Err(EmuError::UnknownInstruction(0xF875)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_opcode_histogram() -> anyhow::Result<()> {
+        let mut emu = Emu::new();
+        emu.load(include_bytes!("../../roms/PONG2"))?;
+        emu.step_n(100)?;
+        assert!(emu.opcode_histogram().is_empty());
+
+        emu.enable_profiling();
+        emu.step_n(5000)?;
+        let histogram = emu.opcode_histogram();
+        assert!(histogram["DXYN"] > 0);
+        assert_eq!(histogram.values().sum::<u64>(), 5000);
+
+        assert_eq!(Instruction::from(0x8124).category(), "8XY4");
+        assert_eq!(Instruction::from(0x5121).category(), "unknown");
+        Ok(())
+    }
+
+    #[test]
+    fn test_sprite_clipping_and_wrapping() -> anyhow::Result<()> {
+        fn lit(emu: &Emu) -> Vec<(usize, usize)> {
+            emu.get_display()
+                .iter()
+                .enumerate()
+                .filter(|(_, &on)| on)
+                .map(|(i, _)| (i % SCREEN_WIDTH, i / SCREEN_WIDTH))
+                .collect()
         }
+
+        // Draw the top row of "0" (0xF0) at (62, 0) and the whole glyph at (0, 30)
+        let rom = [
+            0x60, 0x3E, 0x61, 0x00, 0xA0, 0x00, 0xD0, 0x11, 0x60, 0x00, 0x61, 0x1E, 0xD0, 0x15,
+        ];
+
+        let mut emu = run_rom(&rom, 4)?;
+        assert_eq!(lit(&emu), vec![(62, 0), (63, 0)]);
+        emu.step_n(3)?;
+        // Rows 30 and 31 of the glyph, 0xF0 0x90
+        assert_eq!(
+            lit(&emu)[2..],
+            [(0, 30), (1, 30), (2, 30), (3, 30), (0, 31), (3, 31)]
+        );
+
+        let mut emu = Emu::with_quirks(Quirks {
+            sprite_wrapping: true,
+            ..Quirks::default()
+        });
+        emu.load(&rom)?;
+        emu.step_n(4)?;
+        assert_eq!(lit(&emu), vec![(0, 0), (1, 0), (62, 0), (63, 0)]);
+        emu.step_n(3)?;
+        // The last three rows of the glyph wrap to the top, the first (0x90) turns (0, 0) off
+        let wrapped = lit(&emu);
+        assert!(!wrapped.contains(&(0, 0)));
+        assert!(wrapped.contains(&(3, 0)));
+        assert!(wrapped.contains(&(0, 2)) && wrapped.contains(&(3, 2)));
+        assert_eq!(emu.regs[0xF], 1);
+
+        // The starting position wraps in both modes
+        let emu = run_rom(&[0x60, 0x42, 0x61, 0x21, 0xA0, 0x00, 0xD0, 0x11], 4)?;
+        assert_eq!(lit(&emu), vec![(2, 1), (3, 1), (4, 1), (5, 1)]);
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_display_wait() -> anyhow::Result<()> {
+        // Draw, jump back
+        let rom = [0xD0, 0x11, 0x12, 0x00];
+
+        let mut emu = Emu::new();
+        emu.load(&rom)?;
+        emu.enable_profiling();
+        emu.run_frame()?;
+        assert_eq!(emu.opcode_histogram()["DXYN"], 5);
+
+        let mut emu = Emu::with_quirks(Quirks {
+            display_wait: true,
+            ..Quirks::default()
+        });
+        emu.load(&rom)?;
+        emu.enable_profiling();
+        emu.run_frame()?;
+        assert_eq!(emu.opcode_histogram()["DXYN"], 1);
+        assert_eq!(emu.get_steps(), 1);
+        assert!(!emu.is_waiting_for_vblank());
+
+        assert_eq!(emu.step_n(3)?, 2);
+        assert!(emu.is_waiting_for_vblank());
+        assert_eq!(emu.get_steps(), 3);
+        assert_eq!(emu.step_n(3)?, 0);
+        // The pending wait uses up this frame, the next one draws again
+        emu.run_frame()?;
+        assert_eq!(emu.opcode_histogram()["DXYN"], 2);
+        emu.run_frame()?;
+        assert_eq!(emu.opcode_histogram()["DXYN"], 3);
+        Ok(())
+    }
 
     #[test]
-    fn test_pong2() -> anyhow::Result<()> {
+    fn test_rom_hash() -> anyhow::Result<()> {
+        let pong = include_bytes!("../../roms/PONG2");
+        let mut a = Emu::new();
+        let mut b = Emu::new();
+        a.load(pong)?;
+        b.load(pong)?;
+        assert_eq!(a.rom_hash(), b.rom_hash());
+        // Standard FNV-1a test vector
+        b.load(b"a")?;
+        assert_eq!(b.rom_hash(), 0xaf63dc4c8601ec8c);
+
+        // Trailing zeroes are part of the ROM, not the RAM after it
+        b.reset_and_reload(&[pong.as_slice(), &[0]].concat())?;
+        assert_ne!(a.rom_hash(), b.rom_hash());
+        b.reset_and_reload(pong)?;
+        assert_eq!(a.rom_hash(), b.rom_hash());
+
+        // Self-modifying code doesn't change it
+        a.write_mem(0x200, 0xFF);
+        assert_eq!(a.rom_hash(), b.rom_hash());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rom_len() -> anyhow::Result<()> {
         let mut emu = Emu::new();
-        emu.load(include_bytes!("../../roms/PONG2"));
-        for _i in 0..1000000 {
-            emu.step()?;
+        assert_eq!(emu.rom_len(), 0);
+        assert!(emu.disassemble_rom().is_empty());
+        let rom = include_bytes!("../../roms/PONG2");
+        emu.load(rom)?;
+        let size = rom.len();
+        assert_eq!(emu.rom_len(), size);
+        let listing = emu.disassemble_rom();
+        assert_eq!(listing.len(), size.div_ceil(2));
+        assert_eq!(listing[0].0, 0x200);
+
+        emu.reset_and_reload(&[0x00, 0xE0])?;
+        assert_eq!(emu.rom_len(), 2);
+        assert_eq!(emu.disassemble_rom(), vec![(0x200, "CLS".into())]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_packed() -> anyhow::Result<()> {
+        // I = 0x208, draw 1 row at (V0, V1) = (4, 1), data
+        let rom = [0x60, 0x04, 0x61, 0x01, 0xA2, 0x08, 0xD0, 0x11, 0xB5, 0x00];
+        let emu = run_rom(&rom, 4)?;
+        let packed = emu.display_packed();
+        // 0b10110101 shifted right by 4 pixels spans two bytes of row 1
+        assert_eq!(packed[8..10], [0b0000_1011, 0b0101_0000]);
+        assert_eq!(packed.iter().filter(|&&b| b != 0).count(), 2);
+        // Doubled, it covers rows 2 and 3 from pixel 8
+        let packed = emu.display_packed_hires();
+        assert_eq!(packed[33..35], [0b1100_1111, 0b0011_0011]);
+        assert_eq!(packed[49..51], [0b1100_1111, 0b0011_0011]);
+        assert_eq!(packed.iter().filter(|&&b| b != 0).count(), 4);
+
+        // Hires, V0 = 2, I = 0x208, draw 1 row at (2, 2), data
+        let rom = [0x00, 0xFF, 0x60, 0x02, 0xA2, 0x08, 0xD0, 0x01, 0xC0];
+        let emu = run_rom(&rom, 4)?;
+        let packed = emu.display_packed_hires();
+        assert_eq!(packed[32], 0b0011_0000);
+        assert_eq!(packed.iter().filter(|&&b| b != 0).count(), 1);
+        // Only (2, 2) is sampled at lores, as (1, 1)
+        let packed = emu.display_packed();
+        assert_eq!(packed[8], 0b0100_0000);
+        assert_eq!(packed.iter().filter(|&&b| b != 0).count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_to_string() -> anyhow::Result<()> {
+        // I = font glyph of V0 (0), draw it at (V0, V0)
+        let emu = run_rom(&[0xF0, 0x29, 0xD0, 0x05], 2)?;
+        let text = emu.display_to_string();
+        let rows = text.split('\n').collect::<Vec<_>>();
+        assert_eq!(rows.len(), 32);
+        assert!(rows.iter().all(|row| row.len() == 64));
+        let glyph = rows[..6]
+            .iter()
+            .map(|row| row.trim_end())
+            .collect::<Vec<_>>();
+        assert_eq!(glyph.join("\n"), "####\n#  #\n#  #\n#  #\n####\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_instruction_cycles() -> anyhow::Result<()> {
+        // V0 = 1, draw 5 rows, V1 = 2
+        let mut emu = run_rom(&[0x60, 0x01, 0xD0, 0x05, 0x61, 0x02], 1)?;
+        let load = emu.last_instruction_cycles();
+        emu.step()?;
+        let draw = emu.last_instruction_cycles();
+        assert!(draw > load);
+        emu.step()?;
+        assert_eq!(emu.last_instruction_cycles(), load);
+        assert_eq!(emu.total_cycles(), (2 * load + draw) as u64);
+        assert!(Instruction(0xD00F).cycles() > Instruction(0xD001).cycles());
+
+        emu.reset();
+        assert_eq!(emu.total_cycles(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_frame_report() -> anyhow::Result<()> {
+        // Clear, draw, start the sound timer, then loop until halting on the last jump
+        let rom = [
+            0x00, 0xE0, 0xD0, 0x05, 0x60, 0x02, 0xF0, 0x18, 0x71, 0x01, 0x31, 0x14, 0x12, 0x08,
+            0x12, 0x0E,
+        ];
+        let mut emu = Emu::from_rom(&rom)?;
+        let report = emu.run_frame()?;
+        assert_eq!(
+            report,
+            FrameReport {
+                steps_run: 10,
+                drew: true,
+                cleared: true,
+                beep_started: true,
+                halted: false,
+            }
+        );
+
+        let report = emu.run_frame()?;
+        assert_eq!(report.steps_run, 10);
+        assert!(!report.drew && !report.cleared && !report.beep_started);
+        while !emu.run_frame()?.halted {}
+        assert_eq!(
+            emu.run_frame()?,
+            FrameReport {
+                halted: true,
+                ..FrameReport::default()
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_fade() -> anyhow::Result<()> {
+        // Toggle the top-left pixel once per frame
+        let rom = [0xA2, 0x06, 0xD0, 0x01, 0x12, 0x02, 0x80];
+        let mut emu = Emu::from_rom(&rom)?;
+        emu.set_cycles_per_frame(2);
+        emu.run_frame()?;
+        assert!(emu.get_display()[0]);
+        assert_eq!(emu.display_brightness()[0], 0);
+
+        emu.enable_fade(100);
+        emu.run_frame()?;
+        assert!(!emu.get_display()[0]);
+        assert_eq!(emu.display_brightness()[0], 0);
+        emu.run_frame()?;
+        assert_eq!(emu.display_brightness()[0], 255);
+        emu.run_frame()?;
+        assert!(!emu.get_display()[0]);
+        assert_eq!(emu.display_brightness()[0], 155);
+        assert_eq!(emu.display_brightness()[1], 0);
+
+        // Once it stops being redrawn it fades out completely
+        emu.write_mem(0x204, 0x12);
+        emu.write_mem(0x205, 0x04);
+        emu.run_frame()?;
+        assert_eq!(emu.display_brightness()[0], 55);
+        emu.run_frame()?;
+        assert_eq!(emu.display_brightness()[0], 0);
+
+        emu.enable_fade(0);
+        assert_eq!(emu.display_brightness()[0], 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_clone() -> anyhow::Result<()> {
+        let mut emu = Emu::new();
+        emu.seed_rng(7);
+        emu.load(include_bytes!("../../roms/PONG2"))?;
+        emu.step_n(1000)?;
+        let state = emu.save_state();
+
+        let mut fork = emu.clone();
+        fork.step_n(1000)?;
+        assert_eq!(fork.get_steps(), 2000);
+        assert_eq!(emu.get_steps(), 1000);
+        assert_eq!(emu.save_state(), state);
+
+        // The same inputs give the same results, random numbers included
+        emu.step_n(1000)?;
+        assert_eq!(emu.save_state(), fork.save_state());
+        Ok(())
+    }
+
+    #[derive(Default)]
+    struct RecordingRenderer {
+        frames: Vec<(Vec<bool>, usize, usize)>,
+    }
+
+    impl Renderer for RecordingRenderer {
+        fn present(&mut self, display: &[bool], width: usize, height: usize) {
+            self.frames.push((display.to_vec(), width, height));
         }
+    }
+
+    #[test]
+    fn test_render_with() -> anyhow::Result<()> {
+        // Draw the "0" glyph at (0, 0), switch to hires
+        let mut emu = run_rom(&[0xA0, 0x00, 0xD0, 0x05, 0x00, 0xFF], 2)?;
+        let mut renderer = RecordingRenderer::default();
+        emu.render_with(&mut renderer);
+        emu.step()?;
+        emu.render_with(&mut renderer);
+
+        assert_eq!(renderer.frames.len(), 2);
+        let (display, width, height) = &renderer.frames[0];
+        assert_eq!((*width, *height), (SCREEN_WIDTH, SCREEN_HEIGHT));
+        assert_eq!(display.len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+        // 0xF0: the top row of "0"
+        assert_eq!(&display[..5], &[true, true, true, true, false]);
+        let (display, width, height) = &renderer.frames[1];
+        assert_eq!((*width, *height), (HIRES_WIDTH, HIRES_HEIGHT));
+        assert_eq!(display.as_slice(), emu.get_display());
         Ok(())
     }
 
     #[test]
-    fn test_15puzzle() -> anyhow::Result<()> {
+    fn test_rewind() -> anyhow::Result<()> {
         let mut emu = Emu::new();
-        emu.load(include_bytes!("../../roms/15PUZZLE"));
-        for _i in 0..1000000 {
-            emu.step()?;
+        emu.seed_rng(1);
+        emu.load(include_bytes!("../../roms/PONG2"))?;
+        emu.enable_rewind(10);
+        assert!(!emu.rewind());
+
+        let mut states = Vec::new();
+        for _ in 0..100 {
+            emu.run_frame()?;
+            states.push(emu.save_state());
+        }
+        for _ in 0..5 {
+            assert!(emu.rewind());
+        }
+        // states[i] was captured after frame i + 1
+        assert_eq!(emu.save_state(), states[94]);
+
+        for _ in 0..5 {
+            assert!(emu.rewind());
+        }
+        assert_eq!(emu.save_state(), states[89]);
+        assert!(!emu.rewind());
+        Ok(())
+    }
+
+    struct RecordingBuzzer(Rc<RefCell<Vec<bool>>>);
+
+    impl Buzzer for RecordingBuzzer {
+        fn set_playing(&mut self, on: bool) {
+            self.0.borrow_mut().push(on);
+        }
+    }
+
+    #[test]
+    fn test_buzzer() -> anyhow::Result<()> {
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let mut emu = Emu::new();
+        emu.set_buzzer(Box::new(RecordingBuzzer(calls.clone())));
+        // V0 = 2, ST = V0
+        emu.load(&[0x60, 0x02, 0xF0, 0x18])?;
+        emu.step()?;
+        emu.step()?;
+        assert_eq!(*calls.borrow(), vec![true]);
+        emu.tick_timers();
+        assert_eq!(*calls.borrow(), vec![true]);
+        emu.tick_timers();
+        assert_eq!(emu.st, 0);
+        assert_eq!(*calls.borrow(), vec![true, false]);
+        emu.tick_timers();
+        assert_eq!(*calls.borrow(), vec![true, false]);
+        Ok(())
+    }
+
+    #[cfg(feature = "xo_chip")]
+    #[test]
+    fn test_xo_chip_audio() -> anyhow::Result<()> {
+        struct PatternBuzzer(Rc<RefCell<Vec<AudioPattern>>>);
+
+        impl Buzzer for PatternBuzzer {
+            fn set_playing(&mut self, _on: bool) {}
+
+            fn set_pattern(&mut self, pattern: &AudioPattern) {
+                self.0.borrow_mut().push(*pattern);
+            }
         }
+
+        let patterns = Rc::new(RefCell::new(Vec::new()));
+        let mut emu = Emu::new();
+        emu.set_buzzer(Box::new(PatternBuzzer(patterns.clone())));
+        // I = 0x20A, load pattern, V0 = 112, pitch = V0, jump to self, pattern data
+        let mut rom = vec![0xA2, 0x0A, 0xF0, 0x02, 0x60, 0x70, 0xF0, 0x3A, 0x12, 0x08];
+        rom.extend(0xF0..=0xFF);
+        emu.load(&rom)?;
+        emu.step_n(2)?;
+
+        let expected: Vec<u8> = (0xF0..=0xFF).collect();
+        assert_eq!(emu.audio.samples.as_slice(), expected.as_slice());
+        assert_eq!(patterns.borrow().len(), 1);
+        assert_eq!(patterns.borrow()[0].samples.as_slice(), expected.as_slice());
+        assert_eq!(patterns.borrow()[0].rate(), 4000.0);
+
+        emu.step_n(2)?;
+        assert_eq!(patterns.borrow()[1].pitch, 112);
+        assert_eq!(patterns.borrow()[1].rate(), 8000.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_15puzzle() -> anyhow::Result<()> {
+        let mut emu = Emu::new();
+        emu.load(include_bytes!("../../roms/15PUZZLE"))?;
+        assert_eq!(emu.step_n(1000000)?, 1000000);
         Ok(())
     }
 }
@@ -0,0 +1,53 @@
+use crate::Instruction;
+
+/// The CHIP-8 dialect a ROM is written for, from the most to the least restrictive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Variant {
+    Chip8,
+    /// Uses SUPER-CHIP extensions such as hires mode, scrolling or the big font.
+    SuperChip,
+    /// Uses XO-CHIP extensions such as bit planes or audio patterns.
+    XoChip,
+}
+
+/// Guesses the variant of a ROM by looking for extension opcodes. This is a
+/// heuristic: sprite data can look like an extension opcode, and code at odd
+/// addresses is not looked at.
+pub fn detect_variant(bytes: &[u8]) -> Variant {
+    bytes
+        .chunks_exact(2)
+        .map(
+            |pair| match Instruction::from(u16::from_be_bytes([pair[0], pair[1]])).category() {
+                "F000" | "FN01" | "F002" | "FX3A" => Variant::XoChip,
+                "00CN" | "00FB" | "00FC" | "00FD" | "00FE" | "00FF" | "FX30" | "FX75" | "FX85" => {
+                    Variant::SuperChip
+                }
+                _ => Variant::Chip8,
+            },
+        )
+        .max()
+        .unwrap_or(Variant::Chip8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_variant() {
+        assert_eq!(detect_variant(&[]), Variant::Chip8);
+        assert_eq!(
+            detect_variant(include_bytes!("../../roms/PONG2")),
+            Variant::Chip8
+        );
+        // LD V0, 1; HIGH; JP 0x204
+        assert_eq!(
+            detect_variant(&[0x60, 0x01, 0x00, 0xFF, 0x12, 0x04]),
+            Variant::SuperChip
+        );
+        // SCD 4 and PLANE 3, the XO-CHIP opcode wins
+        assert_eq!(detect_variant(&[0x00, 0xC4, 0xF3, 0x01]), Variant::XoChip);
+        // 0x00FF split across two instructions isn't an opcode
+        assert_eq!(detect_variant(&[0x60, 0x00, 0xFF, 0x12]), Variant::Chip8);
+    }
+}
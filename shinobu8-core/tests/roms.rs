@@ -0,0 +1,73 @@
+//! Runs ROMs from `roms/` for a fixed number of frames and compares the display
+//! with a golden snapshot in `tests/snapshots/`, to catch regressions in opcode
+//! behaviour.
+//!
+//! Only the game ROMs bundled in `roms/` are covered, which exercise the default
+//! quirks. The Timendus test suite (corax+, flags and quirks) isn't in this
+//! repository yet, so quirk behaviour is not validated here. Once its ROMs are
+//! added, each one needs a case with the quirks it tests, and its snapshot has to
+//! be checked by hand against the pass/fail grid it draws.
+//!
+//! Needs the `std` feature to load the ROM files.
+//!
+//! Run with `UPDATE_SNAPSHOTS=1` to write the snapshots after an intended change.
+
+use shinobu8_core::{Emu, Quirks};
+use std::{env, fs, path::Path};
+
+struct Case {
+    rom: &'static str,
+    frames: u32,
+    quirks: Quirks,
+}
+
+fn cases() -> Vec<Case> {
+    vec![
+        Case {
+            rom: "PONG2",
+            frames: 300,
+            quirks: Quirks::default(),
+        },
+        Case {
+            rom: "15PUZZLE",
+            frames: 300,
+            quirks: Quirks::default(),
+        },
+    ]
+}
+
+fn run(case: &Case) -> anyhow::Result<String> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+    let mut emu = Emu::with_quirks(case.quirks);
+    // CXNN must give the same numbers on every run.
+    emu.seed_rng(0);
+    emu.load_file(root.join("roms").join(case.rom))?;
+    for _ in 0..case.frames {
+        emu.run_frame()?;
+    }
+    Ok(emu.display_to_string() + "\n")
+}
+
+#[test]
+fn test_snapshots() -> anyhow::Result<()> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots");
+    let update = env::var_os("UPDATE_SNAPSHOTS").is_some();
+    let mut failed = Vec::new();
+    for case in cases() {
+        let display = run(&case)?;
+        let path = dir.join(format!("{}.txt", case.rom));
+        if update {
+            fs::create_dir_all(&dir)?;
+            fs::write(&path, &display)?;
+        } else if fs::read_to_string(&path).ok().as_ref() != Some(&display) {
+            eprintln!("{} after {} frames:\n{}", case.rom, case.frames, display);
+            failed.push(case.rom);
+        }
+    }
+    assert!(
+        failed.is_empty(),
+        "display differs from snapshot: {:?}",
+        failed
+    );
+    Ok(())
+}
@@ -0,0 +1,237 @@
+use clap::Parser;
+use sdl2::{
+    audio::{AudioCallback, AudioDevice, AudioSpecDesired},
+    event::Event,
+    keyboard::Keycode,
+    pixels::Color,
+    rect::Rect,
+    render::WindowCanvas,
+    AudioSubsystem,
+};
+use shinobu8_core::*;
+use std::time::{Duration, Instant};
+
+// The emulator runs one frame (a batch of instructions and a timer tick) per interval.
+const FRAME_INTERVAL: Duration = Duration::from_nanos(1_000_000_000 / FRAME_RATE as u64);
+
+#[derive(Parser)]
+struct Args {
+    #[arg(short, long)]
+    rom: String,
+    /// Size of a lores pixel in window pixels. Hires pixels are half as big.
+    #[arg(short, long, default_value_t = 10)]
+    scale: u32,
+    /// Brightness an unlit pixel loses per frame, out of 255, to mimic a fading
+    /// phosphor and hide sprite flicker. 0 turns pixels off immediately.
+    #[arg(long, default_value_t = 0)]
+    fade: u8,
+}
+
+fn main() {
+    let args = Args::parse();
+    let scale = args.scale.max(1);
+
+    let sdl = sdl2::init().expect("Failed to initialize SDL.");
+    let window = sdl
+        .video()
+        .expect("Failed to initialize video.")
+        .window(
+            "shinobu8",
+            SCREEN_WIDTH as u32 * scale,
+            SCREEN_HEIGHT as u32 * scale,
+        )
+        .position_centered()
+        .build()
+        .expect("Failed to create window.");
+    // No vsync: the loop below paces frames at 60 Hz itself, whatever the monitor's
+    // refresh rate.
+    let canvas = window
+        .into_canvas()
+        .build()
+        .expect("Failed to create canvas.");
+    let mut screen = Screen { canvas };
+    let mut events = sdl.event_pump().expect("Failed to get event pump.");
+
+    let mut emu = Emu::from_rom_file(&args.rom).expect("Failed to load ROM.");
+    emu.enable_fade(args.fade);
+    // Keep running silently on machines without an audio device.
+    match sdl
+        .audio()
+        .map_err(|e| e.to_string())
+        .and_then(|audio| Beeper::open(&audio))
+    {
+        Ok(beeper) => emu.set_buzzer(Box::new(beeper)),
+        Err(err) => eprintln!("warning: no audio: {}", err),
+    }
+
+    let mut next_frame = Instant::now();
+    'running: loop {
+        for event in events.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => break 'running,
+                Event::KeyDown {
+                    keycode: Some(Keycode::P),
+                    repeat: false,
+                    ..
+                } => emu.set_paused(!emu.is_paused()),
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
+                    ..
+                } => {
+                    if let Some(key) = keypad(keycode) {
+                        emu.key_press(key);
+                    }
+                }
+                Event::KeyUp {
+                    keycode: Some(keycode),
+                    ..
+                } => {
+                    if let Some(key) = keypad(keycode) {
+                        emu.key_release(key);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        emu.run_frame().expect("Failed to execute instruction.");
+        if args.fade > 0 {
+            let (width, height) = emu.display_dimensions();
+            screen.draw(emu.display_brightness(), width, height);
+        } else {
+            emu.render_with(&mut screen);
+        }
+
+        // Don't try to catch up on frames missed while the window was busy.
+        let now = Instant::now();
+        next_frame = (next_frame + FRAME_INTERVAL).max(now);
+        std::thread::sleep(next_frame - now);
+    }
+}
+
+// The keypad on the left of a QWERTY keyboard, same layout as the terminal frontend.
+fn keypad(keycode: Keycode) -> Option<u8> {
+    let key = match keycode {
+        Keycode::Num1 => 0x1,
+        Keycode::Num2 => 0x2,
+        Keycode::Num3 => 0x3,
+        Keycode::Num4 => 0xC,
+        Keycode::Q => 0x4,
+        Keycode::W => 0x5,
+        Keycode::E => 0x6,
+        Keycode::R => 0xD,
+        Keycode::A => 0x7,
+        Keycode::S => 0x8,
+        Keycode::D => 0x9,
+        Keycode::F => 0xE,
+        Keycode::Z => 0xA,
+        Keycode::X => 0x0,
+        Keycode::C => 0xB,
+        Keycode::V => 0xF,
+        _ => return None,
+    };
+    Some(key)
+}
+
+// Draws the display scaled to fill the window, lit pixels white on black.
+struct Screen {
+    canvas: WindowCanvas,
+}
+
+impl Screen {
+    // Draws each pixel as a shade of gray from its brightness.
+    fn draw(&mut self, brightness: &[u8], width: usize, height: usize) {
+        let (window_width, window_height) = self.canvas.output_size().unwrap_or((0, 0));
+        let cell_width = (window_width / width as u32).max(1);
+        let cell_height = (window_height / height as u32).max(1);
+
+        self.canvas.set_draw_color(Color::BLACK);
+        self.canvas.clear();
+        for (i, &level) in brightness.iter().enumerate() {
+            if level == 0 {
+                continue;
+            }
+            let (x, y) = ((i % width) as u32, (i / width) as u32);
+            self.canvas.set_draw_color(Color::RGB(level, level, level));
+            self.canvas
+                .fill_rect(Rect::new(
+                    (x * cell_width) as i32,
+                    (y * cell_height) as i32,
+                    cell_width,
+                    cell_height,
+                ))
+                .expect("Failed to draw.");
+        }
+        self.canvas.present();
+    }
+}
+
+impl Renderer for Screen {
+    fn present(&mut self, display: &[bool], width: usize, height: usize) {
+        let brightness = display
+            .iter()
+            .map(|&on| if on { u8::MAX } else { 0 })
+            .collect::<Vec<_>>();
+        self.draw(&brightness, width, height);
+    }
+}
+
+// A square wave at a fixed pitch, played through SDL audio.
+struct SquareWave {
+    phase: f32,
+    phase_inc: f32,
+    volume: f32,
+}
+
+impl AudioCallback for SquareWave {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            *sample = if self.phase < 0.5 {
+                self.volume
+            } else {
+                -self.volume
+            };
+            self.phase = (self.phase + self.phase_inc) % 1.0;
+        }
+    }
+}
+
+// Plays the square wave while the sound timer is running.
+struct Beeper {
+    device: AudioDevice<SquareWave>,
+}
+
+impl Beeper {
+    const PITCH_HZ: f32 = 440.0;
+
+    fn open(audio: &AudioSubsystem) -> Result<Self, String> {
+        let desired = AudioSpecDesired {
+            freq: Some(44_100),
+            channels: Some(1),
+            samples: None,
+        };
+        let device = audio.open_playback(None, &desired, |spec| SquareWave {
+            phase: 0.0,
+            phase_inc: Self::PITCH_HZ / spec.freq as f32,
+            volume: 0.25,
+        })?;
+        Ok(Beeper { device })
+    }
+}
+
+impl Buzzer for Beeper {
+    fn set_playing(&mut self, on: bool) {
+        if on {
+            self.device.resume();
+        } else {
+            self.device.pause();
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use std::{fmt, str::FromStr};
+
+use unicode_width::UnicodeWidthChar;
+
+/// Characters drawn for lit and unlit pixels, one display row per terminal row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Glyphs {
+    pub on: char,
+    pub off: char,
+}
+
+impl Glyphs {
+    pub fn get(&self, on: bool) -> char {
+        if on {
+            self.on
+        } else {
+            self.off
+        }
+    }
+}
+
+impl FromStr for Glyphs {
+    type Err = String;
+
+    /// Parses the on glyph followed by the off glyph, e.g. `#.` or `█ `.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let chars = s.chars().collect::<Vec<_>>();
+        let [on, off] = chars[..] else {
+            return Err(format!(
+                "expected an on and an off glyph, got {} characters",
+                chars.len()
+            ));
+        };
+        for c in [on, off] {
+            if c.width() != Some(1) {
+                return Err(format!("glyph {:?} is not a single column wide", c));
+            }
+        }
+        Ok(Glyphs { on, off })
+    }
+}
+
+impl fmt::Display for Glyphs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.on, self.off)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("#.".parse(), Ok(Glyphs { on: '#', off: '.' }));
+        assert_eq!(
+            "█ ".parse(),
+            Ok(Glyphs {
+                on: '█', off: ' '
+            })
+        );
+        assert!("#".parse::<Glyphs>().is_err());
+        assert!("#.+".parse::<Glyphs>().is_err());
+        // Wide and zero-width characters would misalign the rows.
+        assert!("忍.".parse::<Glyphs>().is_err());
+        assert!("#\u{301}".parse::<Glyphs>().is_err());
+        assert!("#\t".parse::<Glyphs>().is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use crossterm::event::KeyCode;
+
+/// Maps terminal keys to CHIP-8 keypad keys.
+pub struct Keymap(HashMap<char, u8>);
+
+/// Characters the TUI handles itself before looking up the keypad, so they can't
+/// be mapped: pause, open a ROM, step in debug mode and change the speed. Keep in
+/// sync with the hotkeys in `main`.
+pub const RESERVED: [char; 5] = ['p', 'o', 'n', '+', '-'];
+
+impl Default for Keymap {
+    // Chip-8 keypad layout mapping:
+    // 1 2 3 4 -> 1 2 3 C
+    // Q W E R -> 4 5 6 D
+    // A S D F -> 7 8 9 E
+    // Z X C V -> A 0 B F
+    fn default() -> Self {
+        Keymap(HashMap::from([
+            ('1', 0x1),
+            ('2', 0x2),
+            ('3', 0x3),
+            ('4', 0xC),
+            ('q', 0x4),
+            ('w', 0x5),
+            ('e', 0x6),
+            ('r', 0xD),
+            ('a', 0x7),
+            ('s', 0x8),
+            ('d', 0x9),
+            ('f', 0xE),
+            ('z', 0xA),
+            ('x', 0x0),
+            ('c', 0xB),
+            ('v', 0xF),
+        ]))
+    }
+}
+
+impl Keymap {
+    /// Parses a TOML table of terminal characters to keypad keys, e.g. `q = 0x4`.
+    /// Returns the keymap along with warnings for keypad keys left unmapped.
+    /// Mapping one of the [`RESERVED`] hotkeys is an error.
+    pub fn from_toml(src: &str) -> anyhow::Result<(Self, Vec<String>)> {
+        let table: toml::Table = src.parse()?;
+        let mut map = HashMap::new();
+        let mut mapped = [None; 16];
+        for (name, value) in table {
+            let mut chars = name.chars();
+            let (Some(c), None) = (chars.next(), chars.next()) else {
+                return Err(anyhow::anyhow!("Key {:?} must be a single character", name));
+            };
+            if RESERVED.contains(&c) {
+                return Err(anyhow::anyhow!("Key {:?} is reserved for a TUI hotkey", c));
+            }
+            let key = value
+                .as_integer()
+                .filter(|key| (0..16).contains(key))
+                .ok_or_else(|| anyhow::anyhow!("Key {:?} must map to 0x0..=0xF", name))?
+                as u8;
+            if let Some(other) = mapped[key as usize].replace(c) {
+                return Err(anyhow::anyhow!(
+                    "Keypad key {:X} is mapped to both {:?} and {:?}",
+                    key,
+                    other,
+                    c
+                ));
+            }
+            map.insert(c, key);
+        }
+        let warnings = (0..16)
+            .filter(|key| mapped[*key].is_none())
+            .map(|key| format!("Keypad key {:X} is not mapped", key))
+            .collect();
+        Ok((Keymap(map), warnings))
+    }
+
+    pub fn get(&self, code: KeyCode) -> Option<u8> {
+        match code {
+            KeyCode::Char(c) => self.0.get(&c).copied(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.get(KeyCode::Char('x')), Some(0x0));
+        assert_eq!(keymap.get(KeyCode::Char('v')), Some(0xF));
+        assert_eq!(keymap.get(KeyCode::Char('p')), None);
+        assert_eq!(keymap.get(KeyCode::Esc), None);
+    }
+
+    #[test]
+    fn test_from_toml() -> anyhow::Result<()> {
+        let (keymap, warnings) = Keymap::from_toml("j = 0x5\nk = 0x8\n\"'\" = 15")?;
+        assert_eq!(keymap.get(KeyCode::Char('j')), Some(0x5));
+        assert_eq!(keymap.get(KeyCode::Char('k')), Some(0x8));
+        assert_eq!(keymap.get(KeyCode::Char('\'')), Some(0xF));
+        assert_eq!(keymap.get(KeyCode::Char('x')), None);
+        assert_eq!(warnings.len(), 13);
+        assert_eq!(warnings[0], "Keypad key 0 is not mapped");
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_toml_invalid() {
+        assert!(Keymap::from_toml("j = 0x5\nk = 0x5").is_err());
+        assert!(Keymap::from_toml("jk = 0x5").is_err());
+        assert!(Keymap::from_toml("j = 0x10").is_err());
+        assert!(Keymap::from_toml("j = \"5\"").is_err());
+        assert!(Keymap::from_toml("j = ").is_err());
+        for key in RESERVED {
+            let err = Keymap::from_toml(&format!("\"{}\" = 0x5", key)).err();
+            assert_eq!(
+                err.map(|err| err.to_string()),
+                Some(format!("Key {:?} is reserved for a TUI hotkey", key))
+            );
+        }
+    }
+}
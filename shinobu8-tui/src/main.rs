@@ -1,24 +1,72 @@
+mod glyphs;
+mod keymap;
+mod turbo;
+
 use clap::Parser;
 use crossterm::{
-    event::{
-        poll, 
-        read, 
-        Event, 
-        KeyCode, 
-        KeyEventKind,
-    },
-    terminal::{disable_raw_mode, enable_raw_mode, LeaveAlternateScreen},
+    event::{poll, read, Event, KeyCode, KeyEventKind},
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
-use ratatui::prelude::*;
+use glyphs::Glyphs;
+use keymap::Keymap;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Paragraph},
+};
 use shinobu8_core::*;
-use std::time::Duration;
-use std::io::Stdout;
+use std::io::{Stdout, Write};
+use std::time::{Duration, Instant};
+use turbo::Turbo;
+
+// The emulator runs one frame (a batch of instructions and a timer tick) per interval.
+const FRAME_INTERVAL: Duration = Duration::from_nanos(1_000_000_000 / FRAME_RATE as u64);
+// Upper bound for the speed keys, in instructions per frame.
+const MAX_CYCLES_PER_FRAME: u32 = 100;
 
 #[derive(Parser)]
 struct Args {
     #[arg(short, long)]
     rom: String,
+    /// TOML file mapping terminal keys to keypad keys, e.g. `q = 0x4`.
+    #[arg(long)]
+    keymap: Option<String>,
+    /// Characters for lit and unlit pixels, e.g. `#.`, instead of half blocks.
+    /// Each pixel takes a whole terminal cell, so the screen is twice as tall.
+    #[arg(long)]
+    glyphs: Option<Glyphs>,
+    /// `8XY6`/`8XYE` shift `Vx` in place instead of shifting `Vy` into it.
+    #[arg(long)]
+    shift_quirk: bool,
+    /// `FX55`/`FX65` leave `I` unchanged.
+    #[arg(long)]
+    load_store_quirk: bool,
+    /// `BNNN` jumps to `XNN + VX` instead of `NNN + V0`.
+    #[arg(long)]
+    jump_quirk: bool,
+    /// Sprites crossing the screen edge wrap around instead of being clipped.
+    #[arg(long)]
+    sprite_wrap: bool,
+    /// Skip opcodes that don't decode to an instruction instead of exiting.
+    #[arg(long)]
+    skip_unknown: bool,
+    /// Instructions executed per frame, 60 frames run per second.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    cycles_per_frame: Option<u32>,
+}
+
+impl Args {
+    // The defaults follow the COSMAC VIP, the compatibility flags switch to SUPER-CHIP behavior.
+    fn quirks(&self) -> Quirks {
+        Quirks {
+            shift_uses_vy: !self.shift_quirk,
+            load_store_increments_i: !self.load_store_quirk,
+            jump_uses_vx: self.jump_quirk,
+            sprite_wrapping: self.sprite_wrap,
+            skip_unknown_opcodes: self.skip_unknown,
+            ..Quirks::default()
+        }
+    }
 }
 
 fn main() {
@@ -27,31 +75,134 @@ fn main() {
         println!("Please provide a ROM file.");
         return;
     }
-    enable_raw_mode().expect("Failed to enable raw mode.");
+    let keymap = match &args.keymap {
+        Some(path) => {
+            let result = std::fs::read_to_string(path)
+                .map_err(anyhow::Error::from)
+                .and_then(|src| Keymap::from_toml(&src));
+            let (keymap, warnings) = result.unwrap_or_else(|err| {
+                eprintln!("Failed to load keymap {}: {}", path, err);
+                std::process::exit(1);
+            });
+            for warning in warnings {
+                eprintln!("warning: {}", warning);
+            }
+            keymap
+        }
+        None => Keymap::default(),
+    };
+
+    let mut emu = Emu::with_quirks(args.quirks());
+    if let Some(cycles) = args.cycles_per_frame {
+        emu.set_cycles_per_frame(cycles);
+    }
+    // Report a bad ROM before switching to the alternate screen, so the error stays visible.
+    if let Err(err) = read_rom(&args.rom).and_then(|rom| Ok(emu.load(&rom)?)) {
+        eprintln!("Failed to load {}: {}", args.rom, err);
+        std::process::exit(1);
+    }
+    emu.set_buzzer(Box::new(Bell));
+
+    let _guard = TerminalGuard::enter();
 
     let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))
         .expect("Failed to create terminal.");
     terminal.clear().expect("Failed to clear terminal.");
 
-    let rom = std::fs::read(&args.rom).unwrap();
-    let mut emu = Emu::new();
-    emu.load(&rom);
-
+    let mut stats = Stats::new();
+    let mut inspector = Inspector::default();
+    let mut disassembly = Disassembly::default();
+    let mut prompt = RomPrompt::default();
+    let mut turbo = Turbo::default();
+    let mut next_frame = Instant::now();
     loop {
-        if poll(Duration::from_secs(0)).expect("Failed to poll event.") {
+        let timeout = next_frame.saturating_duration_since(Instant::now());
+        if poll(timeout).expect("Failed to poll event.") {
             let event = read().unwrap();
-            match event {
-                Event::Key(event) => match event.code {
-                    KeyCode::Esc => {
-                        disable_raw_mode().expect("Failed to disable raw mode.");
-                        terminal
-                            .backend_mut()
-                            .execute(LeaveAlternateScreen)
-                            .unwrap();
-                        break;
+            if let Event::Resize(..) = event {
+                // Recenter right away instead of showing a stale layout until the next frame.
+                draw(
+                    &mut terminal,
+                    &emu,
+                    args.glyphs,
+                    &stats,
+                    &inspector,
+                    &disassembly,
+                    &prompt,
+                );
+            }
+            if let Event::Key(event) = event {
+                if event.kind == KeyEventKind::Press {
+                    prompt.message = None;
+                }
+                if prompt.is_open() {
+                    if event.kind != KeyEventKind::Release {
+                        prompt.handle_key(event.code, &mut emu);
+                    }
+                    continue;
+                }
+                match event.code {
+                    KeyCode::Esc => break,
+                    KeyCode::Char('p') if event.kind == KeyEventKind::Press => {
+                        emu.set_paused(!emu.is_paused());
+                    }
+                    KeyCode::Char('o') if event.kind == KeyEventKind::Press => {
+                        prompt.open();
+                    }
+                    KeyCode::F(1) if event.kind == KeyEventKind::Press => {
+                        inspector.debug = !inspector.debug;
+                        if inspector.debug {
+                            inspector.was_paused = emu.is_paused();
+                            emu.set_paused(true);
+                        } else {
+                            emu.set_paused(inspector.was_paused);
+                        }
+                    }
+                    KeyCode::Char('n')
+                        if inspector.debug && event.kind != KeyEventKind::Release =>
+                    {
+                        emu.step().expect("Failed to execute instruction.");
+                    }
+                    KeyCode::F(2) if event.kind == KeyEventKind::Press => {
+                        inspector.visible = !inspector.visible;
+                    }
+                    KeyCode::F(3) if event.kind == KeyEventKind::Press => {
+                        stats.visible = !stats.visible;
+                    }
+                    KeyCode::F(4) if event.kind == KeyEventKind::Press => {
+                        disassembly.visible = !disassembly.visible;
+                    }
+                    KeyCode::Tab => match event.kind {
+                        KeyEventKind::Release => turbo.release(&mut emu),
+                        _ => turbo.hold(&mut emu, Instant::now()),
+                    },
+                    KeyCode::Char('+') | KeyCode::PageUp if event.kind != KeyEventKind::Release => {
+                        let cycles = (turbo.speed(&emu) + 1).min(MAX_CYCLES_PER_FRAME);
+                        turbo.set_speed(&mut emu, cycles);
+                    }
+                    KeyCode::Char('-') | KeyCode::PageDown
+                        if event.kind != KeyEventKind::Release =>
+                    {
+                        let cycles = turbo.speed(&emu) - 1;
+                        turbo.set_speed(&mut emu, cycles);
+                    }
+                    // Scrolls every open panel.
+                    KeyCode::Up
+                        if (inspector.is_shown() || disassembly.visible)
+                            && event.kind != KeyEventKind::Release =>
+                    {
+                        inspector.scroll -= 1;
+                        disassembly.scroll -= 1;
+                    }
+                    KeyCode::Down
+                        if (inspector.is_shown() || disassembly.visible)
+                            && event.kind != KeyEventKind::Release =>
+                    {
+                        inspector.scroll += 1;
+                        disassembly.scroll += 1;
                     }
                     _ => {
-                        if let Some(key) = to_chip8_key(event.code) {
+                        if let Some(key) = keymap.get(event.code) {
                             match event.kind {
                                 KeyEventKind::Press => {
                                     emu.reset_keypad();
@@ -62,80 +213,438 @@ fn main() {
                             }
                         }
                     }
-                },
-                _ => {}
+                }
             }
         }
 
-        emu.cycle().expect("Failed to execute instruction.");
+        let now = Instant::now();
+        if now < next_frame {
+            continue;
+        }
+        // Don't try to catch up on frames missed while the terminal was slow.
+        next_frame = (next_frame + FRAME_INTERVAL).max(now);
+
+        turbo.update(&mut emu, now);
+        emu.run_frame().expect("Failed to execute instruction.");
+        stats.record_frame(emu.get_steps());
 
-        let matrix = emu.get_diaplay();
+        draw(
+            &mut terminal,
+            &emu,
+            args.glyphs,
+            &stats,
+            &inspector,
+            &disassembly,
+            &prompt,
+        );
+    }
+}
+
+// Puts the terminal in raw mode on the alternate screen until dropped. A panic
+// restores the terminal before the message is printed, so it stays readable.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> Self {
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            restore_terminal();
+            hook(info);
+        }));
+        enable_raw_mode().expect("Failed to enable raw mode.");
+        std::io::stdout()
+            .execute(EnterAlternateScreen)
+            .expect("Failed to enter alternate screen.");
+        TerminalGuard
+    }
+}
 
-        draw(&mut terminal, matrix);
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+// Undoes `TerminalGuard::enter` in reverse order. Errors are ignored since this
+// also runs while panicking, and restoring twice is harmless.
+fn restore_terminal() {
+    let _ = std::io::stdout().execute(LeaveAlternateScreen);
+    let _ = disable_raw_mode();
+}
+
+// Frame and instruction rates, averaged over about a second so they don't jitter.
+struct Stats {
+    visible: bool,
+    since: Instant,
+    frames: u32,
+    steps: u64,
+    fps: f64,
+    ips: f64,
+}
+
+impl Stats {
+    fn new() -> Self {
+        Stats {
+            visible: false,
+            since: Instant::now(),
+            frames: 0,
+            steps: 0,
+            fps: 0.0,
+            ips: 0.0,
+        }
+    }
+
+    fn record_frame(&mut self, steps: u64) {
+        self.frames += 1;
+        let elapsed = self.since.elapsed();
+        if elapsed >= Duration::from_secs(1) {
+            let secs = elapsed.as_secs_f64();
+            self.fps = self.frames as f64 / secs;
+            self.ips = steps.saturating_sub(self.steps) as f64 / secs;
+            self.since = Instant::now();
+            self.frames = 0;
+            self.steps = steps;
+        }
+    }
+}
+
+// Reads a ROM file, rejecting empty ones that would just run zeroed memory.
+fn read_rom(path: &str) -> anyhow::Result<Vec<u8>> {
+    let rom = std::fs::read(path)?;
+    if rom.is_empty() {
+        anyhow::bail!("the file is empty");
+    }
+    Ok(rom)
+}
+
+// Modal input line for loading another ROM, opened with `o`.
+#[derive(Default)]
+struct RomPrompt {
+    // The path typed so far, `None` while the prompt is closed.
+    input: Option<String>,
+    // Outcome of the last load, shown until the next key press.
+    message: Option<String>,
+}
+
+impl RomPrompt {
+    fn open(&mut self) {
+        self.input = Some(String::new());
+    }
+
+    fn is_open(&self) -> bool {
+        self.input.is_some()
+    }
+
+    fn handle_key(&mut self, code: KeyCode, emu: &mut Emu) {
+        let Some(input) = &mut self.input else {
+            return;
+        };
+        match code {
+            KeyCode::Enter => {
+                let path = self.input.take().unwrap_or_default();
+                // A bad path or ROM leaves the current game running.
+                let result = read_rom(&path).and_then(|rom| Ok(emu.reset_and_reload(&rom)?));
+                self.message = Some(match result {
+                    Ok(()) => format!("Loaded {}", path),
+                    Err(err) => format!("Failed to load {}: {}", path, err),
+                });
+            }
+            KeyCode::Esc => self.input = None,
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) => input.push(c),
+            _ => {}
+        }
+    }
+
+    fn line(&self) -> Option<String> {
+        match &self.input {
+            Some(input) => Some(format!("Load ROM: {}_", input)),
+            None => self.message.clone(),
+        }
+    }
+}
+
+// Side panel showing registers, the next instruction and a hex dump of memory around PC.
+#[derive(Default)]
+struct Inspector {
+    visible: bool,
+    // Step mode, toggled with F1: the emulator is paused and `n` executes one
+    // instruction. The panel is always shown meanwhile.
+    debug: bool,
+    // Whether the game was already paused with `p` before entering step mode.
+    was_paused: bool,
+    // Hex dump offset from the row containing PC, in rows.
+    scroll: i32,
+}
+
+const INSPECTOR_WIDTH: u16 = 36;
+const DUMP_ROW_BYTES: usize = 8;
+
+impl Inspector {
+    fn is_shown(&self) -> bool {
+        self.visible || self.debug
+    }
+
+    fn render(&self, f: &mut Frame, area: Rect, emu: &Emu) {
+        let mut lines = vec![
+            Line::from(format!(
+                "PC {:04X}  I {:04X}  SP {:X}",
+                emu.get_pc(),
+                emu.get_i(),
+                emu.get_sp()
+            )),
+            Line::from(format!(
+                "DT {:02X}    ST {:02X}",
+                emu.get_delay_timer(),
+                emu.get_sound_timer()
+            )),
+            Line::from(""),
+            Line::from(format!("Next: {}", next_instruction(emu))),
+            Line::from(""),
+        ];
+        for row in 0..4 {
+            let regs = (0..4)
+                .map(|col| {
+                    let i = row * 4 + col;
+                    format!("V{:X} {:02X}", i, emu.get_reg(i))
+                })
+                .collect::<Vec<_>>();
+            lines.push(Line::from(regs.join("  ")));
+        }
+        let stack = emu
+            .call_stack()
+            .iter()
+            .map(|addr| format!("{:04X}", addr))
+            .collect::<Vec<_>>();
+        lines.push(Line::from(format!("Stack: {}", stack.join(" "))));
+        lines.push(Line::from(""));
+
+        // Fill the rest of the panel with memory rows, keeping PC's row in the middle.
+        let rows = (area.height as usize).saturating_sub(lines.len() + 2);
+        let pc = emu.get_pc() as usize;
+        let pc_row = (pc / DUMP_ROW_BYTES) as i64 + self.scroll as i64;
+        let first_row = (pc_row - rows as i64 / 2).max(0) as usize;
+        for row in first_row..first_row + rows {
+            let addr = row * DUMP_ROW_BYTES;
+            let bytes = emu.mem_slice(addr..addr + DUMP_ROW_BYTES);
+            if bytes.is_empty() {
+                break;
+            }
+            let mut spans = vec![Span::raw(format!("{:04X} ", addr))];
+            for (i, byte) in bytes.iter().enumerate() {
+                let style = if addr + i == pc || addr + i == pc + 1 {
+                    Style::default().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::default()
+                };
+                spans.push(Span::raw(" "));
+                spans.push(Span::styled(format!("{:02X}", byte), style));
+            }
+            lines.push(Line::from(spans));
+        }
+
+        let title = if self.debug { "Debug" } else { "Inspector" };
+        let block = Block::default().borders(Borders::ALL).title(title);
+        f.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
+
+// Side panel listing the instructions around PC, toggled with F4.
+#[derive(Default)]
+struct Disassembly {
+    visible: bool,
+    // Offset from PC, in instructions.
+    scroll: i32,
+}
+
+const DISASSEMBLY_WIDTH: u16 = 24;
+
+impl Disassembly {
+    fn render(&self, f: &mut Frame, area: Rect, emu: &Emu) {
+        let rows = area.height.saturating_sub(2) as i64;
+        let pc = emu.get_pc() as i64;
+        // Start on PC's alignment so the instruction at PC decodes as it will run.
+        let first = (pc + (self.scroll as i64 - rows / 2) * 2).max(pc % 2) as usize;
+        let bytes = emu.mem_slice(first..first + rows as usize * 2);
+        let lines = disasm::disassemble_at(bytes, first as u16)
+            .into_iter()
+            .map(|(addr, text)| {
+                let style = if addr as i64 == pc {
+                    Style::default().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::default()
+                };
+                Line::styled(format!("{:04X} {}", addr, text), style)
+            })
+            .collect::<Vec<_>>();
+
+        let block = Block::default().borders(Borders::ALL).title("Disassembly");
+        f.render_widget(Paragraph::new(lines).block(block), area);
     }
 }
 
 fn draw(
     terminal: &mut Terminal<CrosstermBackend<Stdout>>,
-    matrix: &[bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+    emu: &Emu,
+    glyphs: Option<Glyphs>,
+    stats: &Stats,
+    inspector: &Inspector,
+    disassembly: &Disassembly,
+    prompt: &RomPrompt,
 ) {
+    let mut game = Game {
+        glyphs,
+        ..Game::default()
+    };
+    emu.render_with(&mut game);
     terminal
         .draw(|f| {
-            f.render_widget(
-                Game::new(matrix),
-                Rect::new(0, 0, SCREEN_WIDTH as u16, SCREEN_HEIGHT as u16),
-            );
+            let mut screen = f.size();
+            // The prompt takes over the status line while it has something to show,
+            // then the notice that the ROM has finished.
+            let finished = emu
+                .is_halted()
+                .then(|| "ROM finished, press o to load another or Esc to exit".to_string());
+            let debug = inspector
+                .debug
+                .then(|| "Debug: n to step, F1 to resume".to_string());
+            let status = prompt.line().or(finished).or(debug).or_else(|| {
+                stats.visible.then(|| {
+                    format!(
+                        "FPS: {:.0}  IPS: {:.0}  Speed: {}",
+                        stats.fps,
+                        stats.ips,
+                        emu.cycles_per_frame()
+                    )
+                })
+            });
+            if let Some(status) = status.filter(|_| screen.height > 0) {
+                // Reserve the bottom row for the status line.
+                screen.height -= 1;
+                f.render_widget(
+                    Paragraph::new(status),
+                    Rect::new(screen.x, screen.bottom(), screen.width, 1),
+                );
+            }
+            if inspector.is_shown() {
+                let [game, panel] =
+                    Layout::horizontal([Constraint::Min(0), Constraint::Length(INSPECTOR_WIDTH)])
+                        .areas(screen);
+                inspector.render(f, panel, emu);
+                screen = game;
+            }
+            if disassembly.visible {
+                let [game, panel] =
+                    Layout::horizontal([Constraint::Min(0), Constraint::Length(DISASSEMBLY_WIDTH)])
+                        .areas(screen);
+                disassembly.render(f, panel, emu);
+                screen = game;
+            }
+            let area = centered(screen, game.width as u16, game.rows() as u16);
+            f.render_widget(&game, area);
         })
         .expect("Failed to draw.");
 }
 
-struct Game<'a>(&'a [bool; SCREEN_WIDTH * SCREEN_HEIGHT]);
-
-impl Widget for Game<'_> {
-    fn render(self, _area: Rect, buf: &mut Buffer) {
-        for y in 0..SCREEN_HEIGHT {
-            for x in 0..SCREEN_WIDTH {
-                let pixel = self.0[y * SCREEN_WIDTH + x];
-                let style = Style::default().bg(if pixel { Color::White } else { Color::Black });
-                let char = if pixel { " " } else { " " };
-                buf.set_string(x as u16, y as u16, char, style);
-            }
+// Disassembly of the instruction at PC.
+fn next_instruction(emu: &Emu) -> String {
+    let pc = emu.get_pc() as usize;
+    match *emu.mem_slice(pc..pc + 2) {
+        [high, low] => {
+            disasm::disassemble_instruction(Instruction::from(u16::from_be_bytes([high, low])))
         }
+        _ => "-".to_string(),
+    }
+}
+
+// A `width` x `height` rect centered in `area`, shrunk to fit if the area is too small.
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+// Maps a pair of vertically adjacent pixels to a half-block glyph and its colors.
+fn half_block(top: bool, bottom: bool) -> (&'static str, Style) {
+    let style = Style::default().fg(Color::White).bg(Color::Black);
+    match (top, bottom) {
+        (true, true) => ("█", style),
+        (true, false) => ("▀", style),
+        (false, true) => ("▄", style),
+        (false, false) => (" ", style),
     }
 }
 
-impl<'a> Game<'a> {
-    fn new(matrix: &'a [bool; SCREEN_WIDTH * SCREEN_HEIGHT]) -> Self {
-        Game(matrix)
+// Rings the terminal bell when the sound timer starts.
+struct Bell;
+
+impl Buzzer for Bell {
+    fn set_playing(&mut self, on: bool) {
+        if on {
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(b"\x07");
+            let _ = stdout.flush();
+        }
     }
 }
 
-// Chip-8 keypad layout mapping:
-// 1 2 3 4 -> 1 2 3 C
-// Q W E R -> 4 5 6 D
-// A S D F -> 7 8 9 E
-// Z X C V -> A 0 B F
-fn to_chip8_key(key: KeyCode) -> Option<u8> {
-    match key {
-        KeyCode::Char('1') => Some(0x1),
-        KeyCode::Char('2') => Some(0x2),
-        KeyCode::Char('3') => Some(0x3),
-        KeyCode::Char('4') => Some(0xC),
+// The emulator screen as a ratatui widget, filled in by `Emu::render_with`.
+#[derive(Default)]
+struct Game {
+    matrix: Vec<bool>,
+    width: usize,
+    height: usize,
+    // Half blocks are drawn when no glyphs are given.
+    glyphs: Option<Glyphs>,
+}
 
-        KeyCode::Char('q') => Some(0x4),
-        KeyCode::Char('w') => Some(0x5),
-        KeyCode::Char('e') => Some(0x6),
-        KeyCode::Char('r') => Some(0xD),
+impl Game {
+    // Terminal rows needed, half blocks pack two display rows into each.
+    fn rows(&self) -> usize {
+        match self.glyphs {
+            Some(_) => self.height,
+            None => self.height.div_ceil(2),
+        }
+    }
+}
 
-        KeyCode::Char('a') => Some(0x7),
-        KeyCode::Char('s') => Some(0x8),
-        KeyCode::Char('d') => Some(0x9),
-        KeyCode::Char('f') => Some(0xE),
+impl Renderer for Game {
+    fn present(&mut self, display: &[bool], width: usize, height: usize) {
+        self.matrix.clear();
+        self.matrix.extend_from_slice(display);
+        self.width = width;
+        self.height = height;
+    }
+}
 
-        KeyCode::Char('z') => Some(0xA),
-        KeyCode::Char('x') => Some(0x0),
-        KeyCode::Char('c') => Some(0xB),
-        KeyCode::Char('v') => Some(0xF),
-        _ => None,
+impl Widget for &Game {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        // Pixels that don't fit in the terminal are not drawn.
+        if let Some(glyphs) = self.glyphs {
+            for y in 0..area.height as usize {
+                let line = self.matrix[y * self.width..][..area.width as usize]
+                    .iter()
+                    .map(|&on| glyphs.get(on))
+                    .collect::<String>();
+                buf.set_string(area.x, area.y + y as u16, line, Style::default());
+            }
+            return;
+        }
+        for row in 0..area.height as usize {
+            for x in 0..area.width as usize {
+                let y = row * 2;
+                let top = self.matrix[y * self.width + x];
+                let bottom = y + 1 < self.height && self.matrix[(y + 1) * self.width + x];
+                let (glyph, style) = half_block(top, bottom);
+                buf.set_string(area.x + x as u16, area.y + row as u16, glyph, style);
+            }
+        }
     }
 }
@@ -0,0 +1,116 @@
+use std::time::{Duration, Instant};
+
+use shinobu8_core::Emu;
+
+/// Runs the emulator faster while the turbo key is held, e.g. to skip slow intros.
+///
+/// Most terminals only report key presses and auto-repeats, so the key counts as
+/// released once the repeats stop for [`Turbo::TIMEOUT`], or on a release event
+/// where the terminal sends one.
+#[derive(Debug, Default)]
+pub struct Turbo {
+    // Speed to restore and when the key was last seen, while turbo is on.
+    held: Option<(u32, Instant)>,
+}
+
+impl Turbo {
+    /// How many times more instructions run per frame.
+    pub const FACTOR: u32 = 8;
+    /// Longer than the usual delay before a held key starts repeating.
+    pub const TIMEOUT: Duration = Duration::from_millis(700);
+
+    /// Handles a press or repeat of the turbo key.
+    pub fn hold(&mut self, emu: &mut Emu, now: Instant) {
+        let cycles = match self.held {
+            Some((cycles, _)) => cycles,
+            None => {
+                let cycles = emu.cycles_per_frame();
+                emu.set_cycles_per_frame(cycles.saturating_mul(Self::FACTOR));
+                cycles
+            }
+        };
+        self.held = Some((cycles, now));
+    }
+
+    /// Restores the speed from before the key was pressed.
+    pub fn release(&mut self, emu: &mut Emu) {
+        if let Some((cycles, _)) = self.held.take() {
+            emu.set_cycles_per_frame(cycles);
+        }
+    }
+
+    /// Speed without turbo, in instructions per frame.
+    pub fn speed(&self, emu: &Emu) -> u32 {
+        self.held
+            .map_or(emu.cycles_per_frame(), |(cycles, _)| cycles)
+    }
+
+    /// Changes the speed without turbo, keeping turbo on if the key is held.
+    pub fn set_speed(&mut self, emu: &mut Emu, cycles: u32) {
+        match &mut self.held {
+            Some((saved, _)) => {
+                *saved = cycles.max(1);
+                emu.set_cycles_per_frame(saved.saturating_mul(Self::FACTOR));
+            }
+            None => emu.set_cycles_per_frame(cycles),
+        }
+    }
+
+    /// Releases the key if it hasn't repeated for a while, call once per frame.
+    pub fn update(&mut self, emu: &mut Emu, now: Instant) {
+        if let Some((_, last)) = self.held {
+            if now.duration_since(last) > Self::TIMEOUT {
+                self.release(emu);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_turbo() {
+        let mut emu = Emu::new();
+        emu.set_cycles_per_frame(7);
+        let mut turbo = Turbo::default();
+        let start = Instant::now();
+
+        turbo.hold(&mut emu, start);
+        assert_eq!(emu.cycles_per_frame(), 56);
+        // Repeats keep the speed instead of multiplying it again.
+        turbo.hold(&mut emu, start + Duration::from_millis(500));
+        assert_eq!(emu.cycles_per_frame(), 56);
+        turbo.update(&mut emu, start + Duration::from_millis(1000));
+        assert_eq!(emu.cycles_per_frame(), 56);
+        turbo.update(&mut emu, start + Duration::from_millis(1500));
+        assert_eq!(emu.cycles_per_frame(), 7);
+
+        turbo.hold(&mut emu, start);
+        turbo.release(&mut emu);
+        assert_eq!(emu.cycles_per_frame(), 7);
+        turbo.release(&mut emu);
+        assert_eq!(emu.cycles_per_frame(), 7);
+    }
+
+    #[test]
+    fn test_set_speed() {
+        let mut emu = Emu::new();
+        emu.set_cycles_per_frame(20);
+        let mut turbo = Turbo::default();
+        let start = Instant::now();
+
+        turbo.hold(&mut emu, start);
+        assert_eq!(emu.cycles_per_frame(), 160);
+        // `+` while the key is held changes the speed restored on release.
+        turbo.set_speed(&mut emu, 21);
+        assert_eq!(turbo.speed(&emu), 21);
+        assert_eq!(emu.cycles_per_frame(), 168);
+        turbo.release(&mut emu);
+        assert_eq!(emu.cycles_per_frame(), 21);
+
+        turbo.set_speed(&mut emu, 0);
+        assert_eq!(turbo.speed(&emu), 1);
+    }
+}
@@ -0,0 +1,64 @@
+import init, { Emu } from "../pkg/shinobu8_wasm.js";
+
+// Same layout as the terminal frontend: 1234 / QWER / ASDF / ZXCV.
+const KEYS = {
+  "1": 0x1, "2": 0x2, "3": 0x3, "4": 0xc,
+  q: 0x4, w: 0x5, e: 0x6, r: 0xd,
+  a: 0x7, s: 0x8, d: 0x9, f: 0xe,
+  z: 0xa, x: 0x0, c: 0xb, v: 0xf,
+};
+
+const { memory } = await init();
+const emu = new Emu();
+const canvas = document.getElementById("screen");
+const ctx = canvas.getContext("2d");
+let running = false;
+
+// Emulator frames run at 60 Hz whatever the display refresh rate, so time is
+// accumulated between animation frames and spent in 1/60 s steps.
+const FRAME_MS = 1000 / 60;
+// Don't try to catch up after the tab was in the background.
+const MAX_LAG_MS = 5 * FRAME_MS;
+let last = 0;
+let lag = 0;
+
+document.getElementById("rom").addEventListener("change", async (event) => {
+  const rom = new Uint8Array(await event.target.files[0].arrayBuffer());
+  emu.load(rom);
+  if (!running) {
+    running = true;
+    last = performance.now();
+    requestAnimationFrame(frame);
+  }
+});
+
+document.addEventListener("keydown", (event) => {
+  if (event.key in KEYS) emu.key_press(KEYS[event.key]);
+});
+document.addEventListener("keyup", (event) => {
+  if (event.key in KEYS) emu.key_release(KEYS[event.key]);
+});
+
+function frame(now) {
+  lag = Math.min(lag + now - last, MAX_LAG_MS);
+  last = now;
+  while (lag >= FRAME_MS) {
+    emu.run_frame();
+    lag -= FRAME_MS;
+  }
+
+  // The display is one byte per pixel, read it straight out of wasm memory.
+  const width = emu.display_width();
+  const height = emu.display_height();
+  const pixels = new Uint8Array(memory.buffer, emu.display_ptr(), emu.display_len());
+  const image = ctx.createImageData(width, height);
+  for (let i = 0; i < pixels.length; i++) {
+    const v = pixels[i] ? 255 : 0;
+    image.data.set([v, v, v, 255], i * 4);
+  }
+  createImageBitmap(image).then((bitmap) => {
+    ctx.imageSmoothingEnabled = false;
+    ctx.drawImage(bitmap, 0, 0, canvas.width, canvas.height);
+  });
+  requestAnimationFrame(frame);
+}
@@ -0,0 +1,61 @@
+use wasm_bindgen::prelude::*;
+
+/// The emulator exported to JavaScript as `Emu`.
+#[wasm_bindgen(js_name = Emu)]
+pub struct WasmEmu {
+    emu: shinobu8_core::Emu,
+}
+
+impl Default for WasmEmu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen(js_class = Emu)]
+impl WasmEmu {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        WasmEmu {
+            emu: shinobu8_core::Emu::new(),
+        }
+    }
+
+    pub fn load(&mut self, rom: &[u8]) -> Result<(), JsError> {
+        self.emu.reset_and_reload(rom).map_err(JsError::from)
+    }
+
+    /// Runs one 60 Hz frame. Call it once per 1/60 s of elapsed time, not once per
+    /// `requestAnimationFrame` callback, which fires faster on high refresh rate
+    /// displays. See `examples/index.js`.
+    pub fn run_frame(&mut self) -> Result<(), JsError> {
+        self.emu.run_frame().map(|_| ()).map_err(JsError::from)
+    }
+
+    pub fn key_press(&mut self, key: u8) {
+        self.emu.key_press(key);
+    }
+
+    pub fn key_release(&mut self, key: u8) {
+        self.emu.key_release(key);
+    }
+
+    /// Address of the display in wasm memory, one byte per pixel (0 or 1) in
+    /// row-major order. Only valid until the next call into the emulator.
+    pub fn display_ptr(&self) -> *const bool {
+        self.emu.get_display().as_ptr()
+    }
+
+    /// Number of pixels at [`WasmEmu::display_ptr`], `display_width * display_height`.
+    pub fn display_len(&self) -> usize {
+        self.emu.get_display().len()
+    }
+
+    pub fn display_width(&self) -> usize {
+        self.emu.display_dimensions().0
+    }
+
+    pub fn display_height(&self) -> usize {
+        self.emu.display_dimensions().1
+    }
+}